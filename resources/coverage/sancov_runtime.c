@@ -0,0 +1,83 @@
+/*
+ * Coverage runtime for targets fuzzed with `fuzzer --coverage`.
+ *
+ * Link this file into a target built with SanitizerCoverage, and the target
+ * writes the hit count of every edge it executes into the shared memory map
+ * the fuzzer creates for it (its id is passed in the FUZZER_SHM_ID
+ * environment variable). Compile this file on its own, WITHOUT coverage
+ * instrumentation, and link it in:
+ *
+ *   clang -c sancov_runtime.c -o sancov_runtime.o
+ *   clang -fsanitize-coverage=trace-pc-guard target.c sancov_runtime.o -o target
+ *
+ * GCC does not support trace-pc-guard, but its trace-pc mode works as well:
+ *
+ *   gcc -c sancov_runtime.c -o sancov_runtime.o
+ *   gcc -fsanitize-coverage=trace-pc target.c sancov_runtime.o -o target
+ *
+ * Without FUZZER_SHM_ID (like when running the target by hand), hit counts go
+ * into a private map and are ignored.
+ */
+
+#include <stdint.h>
+#include <stdlib.h>
+#include <sys/shm.h>
+
+/* Must match `MAP_SIZE` in src/coverage.rs */
+#define MAP_SIZE (1 << 16)
+#define SHM_ENV_VAR "FUZZER_SHM_ID"
+
+static uint8_t private_map[MAP_SIZE];
+static uint8_t *map = private_map;
+
+static void attach_map(void) {
+    static int attached = 0;
+    if (attached) {
+        return;
+    }
+    attached = 1;
+
+    const char *id = getenv(SHM_ENV_VAR);
+    if (!id) {
+        return;
+    }
+    void *shm = shmat(atoi(id), NULL, 0);
+    if (shm != (void *)-1) {
+        map = shm;
+    }
+}
+
+/* Module constructors may call the guard init before our constructor runs,
+ * so both of them attach. */
+__attribute__((constructor)) static void init(void) { attach_map(); }
+
+/* ---- clang: -fsanitize-coverage=trace-pc-guard ---- */
+
+void __sanitizer_cov_trace_pc_guard_init(uint32_t *start, uint32_t *stop) {
+    static uint32_t next_id = 0;
+    attach_map();
+    if (start == stop || *start) {
+        return;
+    }
+    for (uint32_t *guard = start; guard < stop; guard++) {
+        /* Every guard is one edge, and gets its own slot (while they last) */
+        *guard = next_id++ % MAP_SIZE;
+    }
+}
+
+void __sanitizer_cov_trace_pc_guard(uint32_t *guard) { map[*guard]++; }
+
+/* ---- gcc: -fsanitize-coverage=trace-pc ---- */
+
+/* Start of the executable's mapping, so locations don't change with ASLR */
+extern char __executable_start;
+static __thread uintptr_t previous_location;
+
+void __sanitizer_cov_trace_pc(void) {
+    uintptr_t pc = (uintptr_t)__builtin_return_address(0) - (uintptr_t)&__executable_start;
+    /* Spread the block addresses over the map, and pair them into edges like
+     * AFL does. */
+    uintptr_t location = (pc * 0x9E3779B1u) >> 7;
+    map[(location ^ previous_location) % MAP_SIZE]++;
+    previous_location = location >> 1;
+}
@@ -0,0 +1,147 @@
+/*
+ * Fork server runtime for targets fuzzed with `fuzzer --fork-server`.
+ *
+ * Link this file into the target, and the target no longer has to be started
+ * from scratch for every input: it starts once, stops before main, and forks
+ * a fresh copy of itself whenever the fuzzer asks for one. It speaks the
+ * classic AFL fork server protocol over two file descriptors:
+ *
+ *   - it writes 4 bytes to the status fd (199) to say hello,
+ *   - then for every input, it reads 4 bytes from the control fd (198),
+ *     forks, writes the pid of the child to the status fd, waits for the
+ *     child, and writes its wait status to the status fd.
+ *
+ * The child goes on to run main as usual. Compile this file on its own and
+ * link it in, next to the coverage runtime if you use it:
+ *
+ *   gcc -c forkserver_runtime.c -o forkserver_runtime.o
+ *   gcc target.c forkserver_runtime.o -o target
+ *
+ * Targets can also run in persistent mode, where one copy runs many inputs in
+ * a loop (see fuzzer_loop.h). After every input the copy stops itself with
+ * SIGSTOP, which is reported like an exit, and the next input continues it
+ * instead of forking a new copy. The inputs are in shared memory, whose id is
+ * passed in the FUZZER_INPUT_SHM_ID environment variable.
+ *
+ * Started without the status fd (like when running the target by hand), the
+ * target just runs normally, and a persistent loop runs once with stdin as
+ * its input.
+ */
+
+#include <signal.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <sys/shm.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "fuzzer_loop.h"
+
+/* Must match `CONTROL_FD` and `STATUS_FD` in src/fork_server.rs */
+#define CONTROL_FD 198
+#define STATUS_FD 199
+/* Must match `INPUT_SHM_ENV_VAR` and `MAX_INPUT_LEN` in src/fork_server.rs */
+#define INPUT_SHM_ENV_VAR "FUZZER_INPUT_SHM_ID"
+#define MAX_INPUT_LEN (1 << 20)
+
+/* The layout of the input shared memory. */
+struct shared_input {
+    uint32_t len;
+    unsigned char data[MAX_INPUT_LEN];
+};
+
+static struct shared_input *shared_input = NULL;
+/* Whether we are a copy forked by the fork server. */
+static int forked = 0;
+
+static void attach_input(void) {
+    const char *id = getenv(INPUT_SHM_ENV_VAR);
+    if (!id) {
+        return;
+    }
+    void *shm = shmat(atoi(id), NULL, 0);
+    if (shm != (void *)-1) {
+        shared_input = shm;
+    }
+}
+
+__attribute__((constructor)) static void run_fork_server(void) {
+    uint32_t hello = 0;
+    if (write(STATUS_FD, &hello, 4) != 4) {
+        return;
+    }
+    attach_input();
+    /* A copy in persistent mode that stopped after its last input. */
+    pid_t stopped = -1;
+    for (;;) {
+        uint32_t request;
+        if (read(CONTROL_FD, &request, 4) != 4) {
+            _exit(0);
+        }
+        pid_t child = stopped;
+        if (child > 0) {
+            stopped = -1;
+            kill(child, SIGCONT);
+        } else {
+            child = fork();
+            if (child < 0) {
+                _exit(1);
+            }
+            if (child == 0) {
+                /* In a process group of its own, so the fuzzer can kill the
+                 * subprocesses it starts along with it. */
+                setpgid(0, 0);
+                close(CONTROL_FD);
+                close(STATUS_FD);
+                forked = 1;
+                return;
+            }
+            /* Also here, so it is done before the fuzzer learns the pid. */
+            setpgid(child, child);
+        }
+        int status;
+        if (write(STATUS_FD, &child, 4) != 4 || waitpid(child, &status, WUNTRACED) < 0) {
+            _exit(1);
+        }
+        if (WIFSTOPPED(status)) {
+            stopped = child;
+        }
+        if (write(STATUS_FD, &status, 4) != 4) {
+            _exit(1);
+        }
+    }
+}
+
+int fuzzer_loop(unsigned int max_inputs) {
+    static unsigned int inputs = 0;
+    /* The first input is ready as soon as we start. */
+    if (inputs++ == 0) {
+        return 1;
+    }
+    if (!forked || inputs > max_inputs) {
+        return 0;
+    }
+    raise(SIGSTOP);
+    return 1;
+}
+
+const unsigned char *fuzzer_input(size_t *len) {
+    if (shared_input) {
+        *len = shared_input->len;
+        return shared_input->data;
+    }
+    static unsigned char stdin_input[MAX_INPUT_LEN];
+    static size_t stdin_len = 0;
+    static int read_stdin = 0;
+    if (!read_stdin) {
+        read_stdin = 1;
+        ssize_t n;
+        while (stdin_len < MAX_INPUT_LEN &&
+               (n = read(0, stdin_input + stdin_len, MAX_INPUT_LEN - stdin_len)) > 0) {
+            stdin_len += n;
+        }
+    }
+    *len = stdin_len;
+    return stdin_input;
+}
@@ -0,0 +1,33 @@
+/*
+ * Persistent mode for targets linked with forkserver_runtime.c: one process
+ * runs many inputs in a loop, instead of one process per input.
+ *
+ *   #include "fuzzer_loop.h"
+ *
+ *   int main(void) {
+ *       while (fuzzer_loop(1000)) {
+ *           size_t len;
+ *           const unsigned char *input = fuzzer_input(&len);
+ *           parse(input, len);
+ *       }
+ *       return 0;
+ *   }
+ *
+ * Everything one input leaves behind (globals, leaked memory, open files) is
+ * still there for the next one, so the process is restarted after every
+ * `max_inputs` inputs, and after every crash or timeout.
+ */
+
+#ifndef FUZZER_LOOP_H
+#define FUZZER_LOOP_H
+
+#include <stddef.h>
+
+/* Returns non-zero while there is another input to run, at most
+ * `max_inputs` times in this process. */
+int fuzzer_loop(unsigned int max_inputs);
+
+/* The current input, valid until the next call to fuzzer_loop. */
+const unsigned char *fuzzer_input(size_t *len);
+
+#endif
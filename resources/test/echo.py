@@ -3,4 +3,7 @@
 
 if __name__ == "__main__":
     while True:
-        print(input())
+        try:
+            print(input())
+        except EOFError:
+            break
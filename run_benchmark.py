@@ -1,3 +1,4 @@
+import json
 import os
 import subprocess
 import time
@@ -61,13 +62,36 @@ def build_fuzzer():
     print("done!")
 
 
-def run_fuzzer_on_file(executable_path: str):
+# The version of the --stats-file format this script reads.
+STATS_VERSION = 1
+
+
+def read_stats(stats_path: str):
+    """
+    Reads the statistics the fuzzer left in its --stats-file, or None if there
+    are none (or they are in a format this script does not know).
+    """
+    try:
+        with open(stats_path) as stats_file:
+            stats = json.load(stats_file)
+    except (OSError, ValueError):
+        return None
+    if stats.get("version") != STATS_VERSION:
+        print(f"[WARN] Unknown statistics version {stats.get('version')} in {stats_path}")
+        return None
+    return stats
+
+
+def run_fuzzer_on_file(executable_path: str, stats_path: str):
     """
-    Invokes the Rust fuzzer with --strings <executable_path>.
+    Invokes the Rust fuzzer with --strings <executable_path>, keeping its
+    statistics in stats_path.
     Returns (return_code, stdout, stderr, duration).
     """
     timeout_seconds: float = 30
-    cmd = FUZZER_CMD + [executable_path]
+    cmd = FUZZER_CMD + ["--stats-file", stats_path, executable_path]
+    if os.path.exists(stats_path):
+        os.remove(stats_path)
     start_time = time.perf_counter()
     try:
         completed = subprocess.run(
@@ -115,11 +139,17 @@ def main():
     build_fuzzer()
     for compiled_binary in compiled_paths:
         print(f"\n=== Running fuzzer on '{compiled_binary}' ===")
+        stats_path = f"fuzzer_{os.path.basename(compiled_binary)}.stats.json"
         rc, out, err, duration = run_fuzzer_on_file(
-                compiled_binary if not is_windows() else f"{compiled_binary}.exe"
+                compiled_binary if not is_windows() else f"{compiled_binary}.exe",
+                stats_path
             )
         print(f"Return code     : {rc}" if rc is not None else "TIMEOUT")
         print(f"Execution time  : {duration:.2f} seconds")
+        stats = read_stats(stats_path)
+        if stats is not None:
+            print(f"Executions      : {stats['execs']} ({stats['execs_per_sec']:.0f}/sec)")
+            print(f"Crashes         : {stats['total_crashes']} ({stats['unique_crashes']} unique)")
         print("Fuzzer stdout   :")
         print(out.strip())
         if err.strip():
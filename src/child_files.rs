@@ -0,0 +1,49 @@
+//! Pipes and files shared with children that are not spawned with a
+//! `Command` of their own: copies forked by a fork server, and the children
+//! a harness supervisor forks.
+//!
+//! Their stdin and stderr are temporary files that every child inherits. We
+//! rewrite them before every input, so each child reads just its input and we
+//! read back just what it wrote.
+
+use crate::target::InputFile;
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom},
+    os::{
+        fd::{FromRawFd, OwnedFd},
+        unix::fs::FileExt,
+    },
+};
+
+/// A pipe whose ends are not inherited by anything we exec.
+pub(crate) fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// An open temporary file that is already removed.
+pub(crate) fn scratch_file() -> io::Result<File> {
+    InputFile::create()?.open()
+}
+
+/// Replace the content of a file shared with children, and rewind it. The
+/// file offset is shared too, so the next child starts at the beginning.
+pub(crate) fn rewrite(file: &mut File, content: &[u8]) -> io::Result<()> {
+    file.set_len(0)?;
+    file.write_all_at(content, 0)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
+
+/// What children wrote to a file since it was last rewritten, up to
+/// `max_len` bytes.
+pub(crate) fn read_written(file: &File, max_len: u64) -> io::Result<Vec<u8>> {
+    let len = file.metadata()?.len().min(max_len);
+    let mut written = vec![0; len as usize];
+    file.read_exact_at(&mut written, 0)?;
+    Ok(written)
+}
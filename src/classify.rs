@@ -0,0 +1,197 @@
+//! Telling apart the different ways a target can fail.
+
+use crate::runner::ExitKind;
+use crate::sanitizer;
+
+/// What runtimes print when an allocation fails.
+const OUT_OF_MEMORY_MESSAGES: &[&str] = &[
+    // Python
+    "MemoryError",
+    // AddressSanitizer and libFuzzer
+    "out of memory",
+    "out-of-memory",
+    // Rust
+    "memory allocation of",
+    // C++
+    "std::bad_alloc",
+    // strerror(ENOMEM)
+    "Cannot allocate memory",
+];
+
+/// Whether the target failed because it ran out of memory: an allocation
+/// failed (like under `--mem-limit`), or, if its memory is limited, it was
+/// killed with SIGKILL by someone other than us, which is what the kernel's
+/// OOM killer does. Without a limit, a SIGKILL says nothing about memory.
+///
+/// Targets often echo their input, so we only believe the message where a
+/// failed allocation puts it: in the bug type of a sanitizer report, or on
+/// the last line of stderr. Other sanitizer reports, like an
+/// `allocation-size-too-big`, are bugs rather than running out of memory.
+pub fn is_out_of_memory(exit: ExitKind, stderr: &str, memory_limited: bool) -> bool {
+    let says_out_of_memory = |text: &str| {
+        OUT_OF_MEMORY_MESSAGES
+            .iter()
+            .any(|message| text.contains(message))
+    };
+    match exit {
+        ExitKind::Signal(libc::SIGKILL) if memory_limited => true,
+        ExitKind::Code(0) | ExitKind::Timeout => false,
+        ExitKind::Code(_) | ExitKind::Signal(_) => match sanitizer::parse(stderr) {
+            Some(report) => says_out_of_memory(&report.bug_type),
+            None => stderr
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .is_some_and(says_out_of_memory),
+        },
+    }
+}
+
+/// The short names of the sanitizers, as in their `*_OPTIONS` variables.
+const SANITIZER_NAMES: &[&str] = &["asan", "ubsan", "msan", "lsan", "tsan", "hwasan"];
+
+/// The ways a target can fail on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Failure {
+    /// It was killed by a signal, like SIGSEGV or SIGABRT.
+    Signal,
+    /// A sanitizer reported an error, whatever the exit was.
+    Sanitizer,
+    /// It exited with a non-zero code.
+    ExitCode,
+}
+
+impl Failure {
+    pub const ALL: [Failure; 3] = [Failure::Signal, Failure::Sanitizer, Failure::ExitCode];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Failure::Signal => "signal",
+            Failure::Sanitizer => "sanitizer",
+            Failure::ExitCode => "exit-code",
+        }
+    }
+
+    /// Also takes the short names of the sanitizers, like `asan`, for
+    /// sanitizer failures: their reports all look alike to us.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if SANITIZER_NAMES.contains(&name) {
+            return Some(Failure::Sanitizer);
+        }
+        Self::ALL.into_iter().find(|failure| failure.name() == name)
+    }
+}
+
+/// How the target failed, if it did. Timeouts are not failures of the
+/// target, as we are the ones who killed it.
+pub fn failure(exit: ExitKind, stderr: &str) -> Option<Failure> {
+    match exit {
+        ExitKind::Code(0) | ExitKind::Timeout => None,
+        _ if sanitizer::parse(stderr).is_some() => Some(Failure::Sanitizer),
+        ExitKind::Signal(_) => Some(Failure::Signal),
+        ExitKind::Code(_) => Some(Failure::ExitCode),
+    }
+}
+
+/// Which failures of the target count as crashes. Many programs exit with a
+/// non-zero code on invalid input on purpose, which is not a bug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashPolicy {
+    pub crash_on: Vec<Failure>,
+    /// Exit codes that are fine, unless a sanitizer reported an error.
+    pub ignored_exit_codes: Vec<i32>,
+}
+
+impl Default for CrashPolicy {
+    /// Every failure is a crash.
+    fn default() -> Self {
+        Self {
+            crash_on: Failure::ALL.to_vec(),
+            ignored_exit_codes: Vec::new(),
+        }
+    }
+}
+
+impl CrashPolicy {
+    pub fn is_crash(&self, exit: ExitKind, stderr: &str) -> bool {
+        match (failure(exit, stderr), exit) {
+            (None, _) => false,
+            (Some(Failure::ExitCode), ExitKind::Code(code))
+                if self.ignored_exit_codes.contains(&code) =>
+            {
+                false
+            }
+            (Some(failure), _) => self.crash_on.contains(&failure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_allocation_failures_and_oom_kills() {
+        let python = "Traceback (most recent call last):\n  File \"t.py\", line 3\nMemoryError\n";
+        assert!(is_out_of_memory(ExitKind::Code(1), python, false));
+        let rust = "memory allocation of 1099511627776 bytes failed\n";
+        assert!(is_out_of_memory(ExitKind::Signal(libc::SIGABRT), rust, false));
+        assert!(is_out_of_memory(ExitKind::Signal(libc::SIGKILL), "", true));
+
+        assert!(!is_out_of_memory(ExitKind::Signal(libc::SIGKILL), "", false));
+        assert!(!is_out_of_memory(ExitKind::Signal(libc::SIGSEGV), "Segmentation fault", true));
+        assert!(!is_out_of_memory(ExitKind::Code(1), "Invalid URL", true));
+        assert!(!is_out_of_memory(ExitKind::Code(0), "MemoryError", true));
+
+        let asan = "==7==ERROR: AddressSanitizer: out of memory: allocator is trying to \
+                    allocate 0x10000000000 bytes\n==7==ABORTING\n";
+        assert!(is_out_of_memory(ExitKind::Code(1), asan, false));
+        let too_big = "==7==ERROR: AddressSanitizer: requested allocation size 0xffffffffffff \
+                       exceeds maximum supported size of 0x10000000000 (thread T0)\n\
+                       SUMMARY: AddressSanitizer: allocation-size-too-big\n==7==ABORTING\n";
+        assert!(!is_out_of_memory(ExitKind::Code(1), too_big, false));
+    }
+
+    #[test]
+    fn does_not_believe_echoed_input() {
+        let echoed = "Parsing \"out of memory MemoryError\"\nInvalid URL\n";
+        assert!(!is_out_of_memory(ExitKind::Code(1), echoed, false));
+        let asan = "input: std::bad_alloc\n\
+                    ==7==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602\n\
+                    ==7==ABORTING\n";
+        assert!(!is_out_of_memory(ExitKind::Signal(libc::SIGABRT), asan, false));
+    }
+
+    #[test]
+    fn applies_crash_policy() {
+        let asan = "==42==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602";
+        assert_eq!(failure(ExitKind::Code(1), asan), Some(Failure::Sanitizer));
+        assert_eq!(failure(ExitKind::Signal(libc::SIGABRT), asan), Some(Failure::Sanitizer));
+        assert_eq!(failure(ExitKind::Signal(libc::SIGSEGV), ""), Some(Failure::Signal));
+        assert_eq!(failure(ExitKind::Code(2), "usage: ..."), Some(Failure::ExitCode));
+        assert_eq!(failure(ExitKind::Timeout, asan), None);
+        assert_eq!(Failure::from_name("ubsan"), Some(Failure::Sanitizer));
+        assert_eq!(Failure::from_name("segv"), None);
+
+        let default = CrashPolicy::default();
+        assert!(default.is_crash(ExitKind::Code(1), "Invalid URL"));
+        assert!(!default.is_crash(ExitKind::Code(0), ""));
+
+        let policy = CrashPolicy {
+            crash_on: vec![Failure::Signal, Failure::Sanitizer],
+            ignored_exit_codes: Vec::new(),
+        };
+        assert!(!policy.is_crash(ExitKind::Code(1), "Invalid URL"));
+        assert!(policy.is_crash(ExitKind::Code(1), asan));
+        assert!(policy.is_crash(ExitKind::Signal(libc::SIGFPE), ""));
+
+        let policy = CrashPolicy {
+            ignored_exit_codes: vec![1, 2],
+            ..CrashPolicy::default()
+        };
+        assert!(!policy.is_crash(ExitKind::Code(1), "Invalid URL"));
+        assert!(policy.is_crash(ExitKind::Code(3), ""));
+        assert!(policy.is_crash(ExitKind::Code(1), asan));
+    }
+}
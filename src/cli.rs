@@ -0,0 +1,528 @@
+//! Parsing of the fuzzer's command line.
+
+use fuzzer::{Budget, CrashPolicy, Failure, FuzzingMode};
+use std::{path::PathBuf, time::Duration};
+
+pub const USAGE: &str = "\
+Usage: fuzzer (--strings | --urls) <executable> [options]
+       fuzzer minimize <executable> <input-file> [--out <file>]
+       fuzzer replay <executable> <input-file>... [--fail-on-crash]
+
+The executable can also be given last, after --, together with its arguments:
+       fuzzer (--strings | --urls) [options] -- <executable> [args]...
+       fuzzer replay <input-file>... -- <executable> [args]...
+With --harness, the executable is a libFuzzer harness library instead.
+In the arguments, @@ is replaced with the path of a file holding the input,
+and {input} with the input itself. Otherwise the input is written to stdin,
+unless --input-env is given.
+
+Commands:
+  minimize            Shrink an input that crashes the executable, keeping it
+                      crashing the same way. The result is written to --out,
+                      or next to the input with a .min extension
+  replay              Run the executable once with each input file (or every
+                      file in a directory), and print how it exited. With
+                      --fail-on-crash, exit with an error if any of the inputs
+                      still crash it
+
+Options:
+  --continuous        Keep fuzzing after the first crash, and report every
+                      distinct crashing input at the end
+  --max-time <secs>   Stop fuzzing after this many seconds, and print a report
+  --max-execs <n>     Stop fuzzing after this many executions, and print a
+                      report. Ctrl-C and SIGTERM stop fuzzing the same way
+  --output <dir>      Save every crashing input to this directory as a raw
+                      file named by its hash, with a .meta file next to it
+  --stats-file <file> Keep a JSON snapshot of the run's statistics in this file,
+                      rewritten every second and once at the end (see the
+                      README for the format)
+  --events <file>     Write what happens during the run to this file as JSON
+                      lines: the start, every new crash, hang or
+                      out-of-memory failure, new coverage, and the end
+  --corpus <dir>      Try every file in this directory as an input first, and
+                      keep mutating them later on
+  --coverage          Collect edge coverage from the executable, and mutate
+                      the inputs that reach new edges. The executable must be
+                      linked with resources/coverage/sancov_runtime.c
+  --fork-server       Start the executable once, and have it fork a copy of
+                      itself for every input, which is much faster. The
+                      executable must be linked with
+                      resources/forkserver/forkserver_runtime.c, otherwise
+                      it is run the usual way. Executables looping on
+                      fuzzer_loop (see resources/forkserver/fuzzer_loop.h)
+                      run many inputs per copy, in persistent mode
+  --harness           The executable is a shared library exporting
+                      LLVMFuzzerTestOneInput, like a libFuzzer harness. It is
+                      loaded into the fuzzer and called with every input in a
+                      forked child, which is replaced when it crashes. It
+                      takes no arguments or environment, and does not report
+                      coverage. Works for every command
+  --seed <n>          Seed the random generators with this number, to repeat
+                      an earlier run. A random seed is printed otherwise
+  --timeout <secs>    Kill the executable if a single run takes longer than
+                      this (default 1.05). Works for every command
+  --hangs             Report inputs that time out as hangs, once they time out
+                      again with a 4 times longer timeout. Hangs are saved to
+                      --output as hang-<hash>, next to the crashes
+  --jobs <n>          Run this many workers at once, each generating different
+                      inputs. They share the crashes found and the budget
+  --input-env <name>  Pass the input in this environment variable instead of
+                      stdin. It is cut short at the first NUL byte, and
+                      inputs too large for an environment variable are
+                      skipped (this works for every command)
+  --mem-limit <MiB>   Limit the memory of the executable. Inputs that make it
+                      run out of memory are saved as oom-<hash> instead of
+                      crashes. This limits the address space, which builds
+                      with AddressSanitizer reserve plenty of, so leave it
+                      off for them. Works for every command
+  --crash-on <kinds>  Which failures of the executable are crashes, as a comma
+                      separated list of: signal (killed by a signal, like
+                      SIGSEGV), sanitizer (a sanitizer reported an error, also
+                      written asan, ubsan, msan, ...) and exit-code (a
+                      non-zero exit code). All of them by default. Works for
+                      every command
+  --ignore-exit-codes <codes>
+                      Exit codes that are not crashes, like 1,2 for a program
+                      that exits with them on invalid input, unless a
+                      sanitizer reported an error. Works for every command
+  --env <key=value>   Set an environment variable for the executable. Can be
+                      given more than once. Sanitizer options (like
+                      ASAN_OPTIONS) are added after our defaults, so they win.
+                      Works for every command
+  --clear-env         Do not pass our environment on to the executable, only
+                      the variables given with --env, to make runs
+                      reproducible on other machines. Works for every
+                      command";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Generate inputs and run the executable with them.
+    Fuzz { mode: FuzzingMode },
+    /// Shrink a crashing input.
+    Minimize { input: PathBuf, output: PathBuf },
+    /// Run saved inputs again.
+    Replay {
+        inputs: Vec<PathBuf>,
+        fail_on_crash: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub executable: PathBuf,
+    /// The executable's arguments, which may contain input placeholders.
+    pub target_args: Vec<String>,
+    pub continuous: bool,
+    pub budget: Budget,
+    pub output: Option<PathBuf>,
+    /// Where to keep JSON statistics of the run.
+    pub stats_file: Option<PathBuf>,
+    /// Where to log the events of the run as JSON lines.
+    pub events: Option<PathBuf>,
+    pub corpus: Option<PathBuf>,
+    pub coverage: bool,
+    pub seed: Option<u64>,
+    /// How many workers fuzz at once.
+    pub jobs: usize,
+    /// The timeout of a single execution, if not the default.
+    pub timeout: Option<Duration>,
+    pub hangs: bool,
+    pub fork_server: bool,
+    /// The executable is a harness library, run in-process.
+    pub harness: bool,
+    pub input_env: Option<String>,
+    /// The memory limit of the target in MiB.
+    pub mem_limit: Option<u64>,
+    pub crash_policy: CrashPolicy,
+    /// Environment variables for the target, in the order given.
+    pub env: Vec<(String, String)>,
+    pub clear_env: bool,
+}
+
+/// Take the value that must follow an option like `--max-time`.
+fn value_of(option: &str, args: &mut impl Iterator<Item = String>) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for option {}.", option))
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for option {}: {}", option, value))
+}
+
+fn parse_secs(option: &str, value: &str) -> Result<Duration, String> {
+    let secs: f64 = parse_number(option, value)?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| format!("Invalid value for option {}: {}", option, value))
+}
+
+impl Options {
+    /// Parse the command line arguments, not including the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let subcommand = args.next_if(|arg| arg == "minimize" || arg == "replay");
+        let is_minimize = subcommand.as_deref() == Some("minimize");
+        let is_replay = subcommand.as_deref() == Some("replay");
+        let mut mode = None;
+        let mut positional = Vec::new();
+        let mut out = None;
+        let mut output = None;
+        let mut stats_file = None;
+        let mut events = None;
+        let mut corpus = None;
+        let mut continuous = false;
+        let mut fail_on_crash = false;
+        let mut coverage = false;
+        let mut seed = None;
+        let mut target_command = None;
+        let mut input_env = None;
+        let mut jobs = 1;
+        let mut timeout = None;
+        let mut hangs = false;
+        let mut fork_server = false;
+        let mut harness = false;
+        let mut mem_limit = None;
+        let mut crash_policy = CrashPolicy::default();
+        let mut env = Vec::new();
+        let mut clear_env = false;
+        let mut budget = Budget::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => target_command = Some(args.by_ref().collect::<Vec<_>>()),
+                "--continuous" if subcommand.is_none() => continuous = true,
+                "--max-time" if subcommand.is_none() => {
+                    budget.max_time = Some(parse_secs(&arg, &value_of(&arg, &mut args)?)?)
+                }
+                "--max-execs" if subcommand.is_none() => {
+                    budget.max_execs = Some(parse_number(&arg, &value_of(&arg, &mut args)?)?)
+                }
+                "--fail-on-crash" if is_replay => fail_on_crash = true,
+                "--coverage" if subcommand.is_none() => coverage = true,
+                "--output" if subcommand.is_none() => {
+                    output = Some(PathBuf::from(value_of(&arg, &mut args)?))
+                }
+                "--stats-file" if subcommand.is_none() => {
+                    stats_file = Some(PathBuf::from(value_of(&arg, &mut args)?))
+                }
+                "--events" if subcommand.is_none() => {
+                    events = Some(PathBuf::from(value_of(&arg, &mut args)?))
+                }
+                "--corpus" if subcommand.is_none() => {
+                    corpus = Some(PathBuf::from(value_of(&arg, &mut args)?))
+                }
+                "--seed" if subcommand.is_none() => {
+                    seed = Some(parse_number(&arg, &value_of(&arg, &mut args)?)?)
+                }
+                "--jobs" if subcommand.is_none() => {
+                    jobs = parse_number(&arg, &value_of(&arg, &mut args)?)?;
+                    if jobs == 0 {
+                        return Err("Invalid value for option --jobs: 0".to_string());
+                    }
+                }
+                "--timeout" => {
+                    let secs = parse_secs(&arg, &value_of(&arg, &mut args)?)?;
+                    if secs.is_zero() {
+                        return Err("Invalid value for option --timeout: 0".to_string());
+                    }
+                    timeout = Some(secs);
+                }
+                "--hangs" if subcommand.is_none() => hangs = true,
+                "--fork-server" if subcommand.is_none() => fork_server = true,
+                "--harness" => harness = true,
+                "--input-env" => {
+                    let name = value_of(&arg, &mut args)?;
+                    if name.is_empty() || name.contains(['=', '\0']) {
+                        return Err(format!("Invalid environment variable name: {:?}", name));
+                    }
+                    input_env = Some(name);
+                }
+                "--mem-limit" => {
+                    let mib: u64 = parse_number(&arg, &value_of(&arg, &mut args)?)?;
+                    if mib == 0 {
+                        return Err("Invalid value for option --mem-limit: 0".to_string());
+                    }
+                    mem_limit = Some(mib);
+                }
+                "--crash-on" => {
+                    crash_policy.crash_on = value_of(&arg, &mut args)?
+                        .split(',')
+                        .map(|name| {
+                            Failure::from_name(name)
+                                .ok_or_else(|| format!("Invalid value for option --crash-on: {}", name))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "--ignore-exit-codes" => {
+                    let codes = value_of(&arg, &mut args)?;
+                    crash_policy.ignored_exit_codes = codes
+                        .split(',')
+                        .map(|code| parse_number(&arg, code))
+                        .collect::<Result<_, _>>()?;
+                }
+                "--env" => {
+                    let variable = value_of(&arg, &mut args)?;
+                    let (key, value) = variable
+                        .split_once('=')
+                        .filter(|(key, _)| !key.is_empty())
+                        .ok_or_else(|| format!("Invalid value for option --env: {}", variable))?;
+                    env.push((key.to_string(), value.to_string()));
+                }
+                "--clear-env" => clear_env = true,
+                "--out" if is_minimize => out = Some(PathBuf::from(value_of(&arg, &mut args)?)),
+                _ if arg.starts_with("--") && subcommand.is_none() => {
+                    mode = Some(FuzzingMode::from_arg(&arg)?)
+                }
+                _ if arg.starts_with("--") => return Err(format!("Invalid option: {}", arg)),
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let (executable, target_args) = match target_command {
+            Some(mut command) if !command.is_empty() => {
+                let executable = PathBuf::from(command.remove(0));
+                (executable, command)
+            }
+            Some(_) => return Err("Missing executable after --.".to_string()),
+            None => (positional.next().ok_or("Missing executable.")?, Vec::new()),
+        };
+        let command = if is_minimize {
+            let input = positional.next().ok_or("Missing input file to minimize.")?;
+            let output = out.unwrap_or_else(|| input.with_extension("min"));
+            Command::Minimize { input, output }
+        } else if is_replay {
+            let inputs: Vec<PathBuf> = positional.by_ref().collect();
+            if inputs.is_empty() {
+                return Err("Missing input files to replay.".to_string());
+            }
+            Command::Replay {
+                inputs,
+                fail_on_crash,
+            }
+        } else {
+            Command::Fuzz {
+                mode: mode.ok_or("Missing fuzzing mode. Use --strings or --urls.")?,
+            }
+        };
+        if let Some(unexpected) = positional.next() {
+            return Err(format!("Unexpected argument: {}", unexpected.display()));
+        }
+        if harness && !target_args.is_empty() {
+            return Err("A harness takes no arguments.".to_string());
+        }
+        if harness && (input_env.is_some() || !env.is_empty() || clear_env) {
+            return Err("A harness takes no environment of its own.".to_string());
+        }
+        if harness && (fork_server || coverage) {
+            return Err("--harness cannot be used with --fork-server or --coverage.".to_string());
+        }
+
+        Ok(Self {
+            command,
+            executable,
+            target_args,
+            continuous,
+            budget,
+            output,
+            stats_file,
+            events,
+            corpus,
+            coverage,
+            seed,
+            jobs,
+            timeout,
+            hangs,
+            fork_server,
+            harness,
+            input_env,
+            mem_limit,
+            crash_policy,
+            env,
+            clear_env,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_mode_and_executable() {
+        let options = parse(&["--urls", "./target"]).unwrap();
+        assert_eq!(options.command, Command::Fuzz { mode: FuzzingMode::Urls });
+        assert_eq!(options.executable, PathBuf::from("./target"));
+        assert!(!options.continuous);
+        assert!(!options.coverage);
+        assert_eq!(options.seed, None);
+        assert_eq!(options.jobs, 1);
+        assert_eq!(options.timeout, None);
+        assert!(!options.hangs);
+        assert!(!options.fork_server);
+        assert!(!options.harness);
+        assert_eq!(options.crash_policy, CrashPolicy::default());
+        assert_eq!(options.budget, Budget::default());
+    }
+
+    #[test]
+    fn parses_continuous_budget() {
+        let options = parse(&[
+            "--strings", "./target", "--continuous", "--max-time", "1.5", "--max-execs", "100",
+            "--coverage", "--seed", "42", "--jobs", "4", "--timeout", "0.25", "--hangs",
+            "--fork-server",
+        ])
+        .unwrap();
+        assert_eq!(options.timeout, Some(Duration::from_millis(250)));
+        assert!(options.hangs);
+        assert!(options.fork_server);
+        assert_eq!(options.jobs, 4);
+        assert!(options.continuous);
+        assert!(options.coverage);
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.budget.max_time, Some(Duration::from_millis(1500)));
+        assert_eq!(options.budget.max_execs, Some(100));
+    }
+
+    #[test]
+    fn parses_directories() {
+        let options =
+            parse(&["--strings", "./target", "--output", "crashes", "--corpus", "seeds"]).unwrap();
+        assert_eq!(options.output, Some(PathBuf::from("crashes")));
+        assert_eq!(options.corpus, Some(PathBuf::from("seeds")));
+        assert_eq!(options.stats_file, None);
+        let options = parse(&[
+            "--strings", "./target", "--stats-file", "stats.json", "--events", "events.jsonl",
+        ])
+        .unwrap();
+        assert_eq!(options.stats_file, Some(PathBuf::from("stats.json")));
+        assert_eq!(options.events, Some(PathBuf::from("events.jsonl")));
+    }
+
+    #[test]
+    fn parses_target_command() {
+        let options = parse(&["--urls", "--continuous", "--", "./target", "--parse", "@@"]).unwrap();
+        assert_eq!(options.command, Command::Fuzz { mode: FuzzingMode::Urls });
+        assert!(options.continuous);
+        assert_eq!(options.executable, PathBuf::from("./target"));
+        assert_eq!(options.target_args, ["--parse", "@@"]);
+
+        let options = parse(&["replay", "crash", "--", "./target", "{input}"]).unwrap();
+        assert_eq!(options.executable, PathBuf::from("./target"));
+        assert_eq!(options.target_args, ["{input}"]);
+        assert!(matches!(options.command, Command::Replay { inputs, .. } if inputs == [PathBuf::from("crash")]));
+
+        let options = parse(&["replay", "./target", "crash", "--input-env", "LANG"]).unwrap();
+        assert_eq!(options.input_env.as_deref(), Some("LANG"));
+
+        assert!(parse(&["--urls", "--"]).is_err());
+        assert!(parse(&["--urls", "./target", "--input-env", "A=B"]).is_err());
+
+        let options = parse(&["minimize", "./target", "crash", "--mem-limit", "512"]).unwrap();
+        assert_eq!(options.mem_limit, Some(512));
+        assert!(parse(&["--urls", "./target", "--mem-limit", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_crash_policy() {
+        let options = parse(&[
+            "replay", "./target", "crash", "--crash-on", "signal,sanitizer", "--ignore-exit-codes",
+            "1,2",
+        ])
+        .unwrap();
+        assert_eq!(options.crash_policy.crash_on, [Failure::Signal, Failure::Sanitizer]);
+        assert_eq!(options.crash_policy.ignored_exit_codes, [1, 2]);
+
+        let options = parse(&["--urls", "./target", "--crash-on", "signal,asan"]).unwrap();
+        assert_eq!(options.crash_policy.crash_on, [Failure::Signal, Failure::Sanitizer]);
+        assert!(parse(&["--urls", "./target", "--crash-on", "signal,segv"]).is_err());
+        assert!(parse(&["--urls", "./target", "--ignore-exit-codes", "1,x"]).is_err());
+    }
+
+    #[test]
+    fn parses_env() {
+        let options = parse(&[
+            "--urls", "--env", "ASAN_OPTIONS=detect_leaks=1", "--env", "EMPTY=", "--clear-env",
+            "--", "./target",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.env,
+            [
+                ("ASAN_OPTIONS".to_string(), "detect_leaks=1".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+        assert!(options.clear_env);
+
+        assert!(parse(&["--urls", "./target", "--env", "NOVALUE"]).is_err());
+        assert!(parse(&["--urls", "./target", "--env", "=value"]).is_err());
+        assert!(parse(&["--urls", "./other", "--", "./target"]).is_err());
+    }
+
+    #[test]
+    fn parses_minimize() {
+        let options = parse(&["minimize", "./target", "crash.bin"]).unwrap();
+        assert_eq!(options.executable, PathBuf::from("./target"));
+        assert_eq!(
+            options.command,
+            Command::Minimize {
+                input: PathBuf::from("crash.bin"),
+                output: PathBuf::from("crash.min"),
+            }
+        );
+
+        let options = parse(&["minimize", "./target", "crash.bin", "--out", "small"]).unwrap();
+        assert!(matches!(options.command, Command::Minimize { output, .. } if output == Path::new("small")));
+    }
+
+    #[test]
+    fn parses_replay() {
+        let options = parse(&["replay", "./target", "a", "b", "--fail-on-crash"]).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Replay {
+                inputs: vec![PathBuf::from("a"), PathBuf::from("b")],
+                fail_on_crash: true,
+            }
+        );
+        assert!(parse(&["replay", "./target"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["./target"]).is_err());
+        assert!(parse(&["--strings"]).is_err());
+        assert!(parse(&["--strings", "./target", "--max-execs"]).is_err());
+        assert!(parse(&["--strings", "./target", "--max-time", "soon"]).is_err());
+        assert!(parse(&["--strings", "./target", "--seed", "-1"]).is_err());
+        assert!(parse(&["--strings", "./target", "--jobs", "0"]).is_err());
+        assert!(parse(&["--strings", "./target", "--timeout", "0"]).is_err());
+        assert!(parse(&["replay", "./target", "crash", "--hangs"]).is_err());
+        assert!(parse(&["minimize", "./target", "crash", "--fork-server"]).is_err());
+        assert!(parse(&["minimize", "./target", "crash", "--continuous"]).is_err());
+        assert!(parse(&["minimize", "./target", "crash", "--max-time", "1"]).is_err());
+        assert!(parse(&["minimize", "./target", "crash", "--max-execs", "1"]).is_err());
+        assert!(parse(&["replay", "./target", "crash", "--events", "x"]).is_err());
+        assert!(parse(&["--bogus", "./target"]).is_err());
+        assert!(parse(&["--strings", "./target", "another"]).is_err());
+        assert!(parse(&["minimize", "./target"]).is_err());
+        assert!(parse(&["--strings", "./target", "--out", "x"]).is_err());
+    }
+
+    #[test]
+    fn parses_harness() {
+        let options = parse(&["replay", "./libharness.so", "crash", "--harness"]).unwrap();
+        assert!(options.harness);
+        assert!(parse(&["--urls", "--harness", "--", "./libharness.so", "@@"]).is_err());
+        assert!(parse(&["--urls", "./libharness.so", "--harness", "--env", "A=1"]).is_err());
+        assert!(parse(&["--urls", "./libharness.so", "--harness", "--fork-server"]).is_err());
+    }
+}
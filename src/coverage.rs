@@ -0,0 +1,236 @@
+//! Edge coverage feedback from targets built with SanitizerCoverage and
+//! linked with `resources/coverage/sancov_runtime.c`.
+//!
+//! The target writes edge hit counts into a SysV shared memory map that we
+//! create for it. After every execution the counts are bucketed like AFL does
+//! (1, 2, 3, 4-7, 8-15, ...) and compared with everything seen so far, so an
+//! input is interesting if it reaches a new edge, or hits a known edge a new
+//! number of times.
+
+use std::{
+    io, ptr,
+    sync::{Arc, Mutex},
+};
+
+/// Must match `MAP_SIZE` in the runtime.
+pub const MAP_SIZE: usize = 1 << 16;
+/// The environment variable the runtime reads the shared memory id from.
+pub const SHM_ENV_VAR: &str = "FUZZER_SHM_ID";
+
+/// A SysV shared memory segment, removed on drop.
+#[derive(Debug)]
+pub(crate) struct SharedMap {
+    id: libc::c_int,
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The map is only accessed through `&mut self` (or `&self` for reading), like
+// any owned buffer.
+unsafe impl Send for SharedMap {}
+
+impl SharedMap {
+    pub(crate) fn new(len: usize) -> io::Result<Self> {
+        let id = unsafe {
+            libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | libc::IPC_EXCL | 0o600)
+        };
+        if id < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = unsafe { libc::shmat(id, ptr::null(), 0) };
+        if ptr as isize == -1 {
+            let error = io::Error::last_os_error();
+            unsafe { libc::shmctl(id, libc::IPC_RMID, ptr::null_mut()) };
+            return Err(error);
+        }
+        Ok(Self {
+            id,
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// The id the target attaches the segment with.
+    pub(crate) fn id(&self) -> libc::c_int {
+        self.id
+    }
+
+    fn clear(&mut self) {
+        self.as_mut_slice().fill(0);
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for SharedMap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::shmdt(self.ptr as *const libc::c_void);
+            libc::shmctl(self.id, libc::IPC_RMID, ptr::null_mut());
+        }
+    }
+}
+
+/// Bucket a hit count, so that small changes in loop counts are not news.
+fn classify_count(count: u8) -> u8 {
+    match count {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        3 => 4,
+        4..=7 => 8,
+        8..=15 => 16,
+        16..=31 => 32,
+        32..=127 => 64,
+        128..=255 => 128,
+    }
+}
+
+/// Compare the hit counts of one execution with `virgin`, the bits of every
+/// bucket that was never seen yet, and clear the ones seen now. Returns
+/// whether anything new was seen.
+fn merge_new_bits(virgin: &mut [u8], trace: &[u8]) -> bool {
+    let mut found_new = false;
+    for (virgin, &count) in virgin.iter_mut().zip(trace) {
+        if count == 0 {
+            continue;
+        }
+        let bucket = classify_count(count);
+        if *virgin & bucket != 0 {
+            *virgin &= !bucket;
+            found_new = true;
+        }
+    }
+    found_new
+}
+
+/// Everything seen so far, which may be shared by several `Coverage`s.
+#[derive(Debug)]
+struct Progress {
+    virgin: Vec<u8>,
+    /// How many executions found new coverage.
+    interesting_execs: usize,
+}
+
+/// Collects coverage from the target's executions.
+#[derive(Debug)]
+pub struct Coverage {
+    map: SharedMap,
+    progress: Arc<Mutex<Progress>>,
+    /// Set once any execution reported coverage, to tell instrumented targets
+    /// apart.
+    saw_any: bool,
+}
+
+impl Coverage {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            map: SharedMap::new(MAP_SIZE)?,
+            progress: Arc::new(Mutex::new(Progress {
+                virgin: vec![0xff; MAP_SIZE],
+                interesting_execs: 0,
+            })),
+            saw_any: false,
+        })
+    }
+
+    /// Only count coverage as new if neither of us has seen it before, for
+    /// parallel runs of the same target.
+    pub fn share_progress_with(&mut self, other: &Coverage) {
+        self.progress = other.progress.clone();
+    }
+
+    /// The id the target needs in `SHM_ENV_VAR` to report to us.
+    pub fn shm_id(&self) -> i32 {
+        self.map.id()
+    }
+
+    /// Call before every execution.
+    pub fn reset(&mut self) {
+        self.map.clear();
+    }
+
+    /// Call after every execution. Returns whether it found new coverage.
+    pub fn update(&mut self) -> bool {
+        let trace = self.map.as_slice();
+        if !self.saw_any && trace.iter().any(|&count| count != 0) {
+            self.saw_any = true;
+        }
+        let mut progress = self.progress.lock().unwrap();
+        let found_new = merge_new_bits(&mut progress.virgin, trace);
+        if found_new {
+            progress.interesting_execs += 1;
+        }
+        found_new
+    }
+
+    /// Whether any execution so far reported coverage.
+    pub fn saw_any(&self) -> bool {
+        self.saw_any
+    }
+
+    /// The number of edges that were hit by any execution.
+    pub fn covered_edges(&self) -> usize {
+        let progress = self.progress.lock().unwrap();
+        progress.virgin.iter().filter(|&&bits| bits != 0xff).count()
+    }
+
+    pub fn interesting_execs(&self) -> usize {
+        self.progress.lock().unwrap().interesting_execs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_bits_only_once_per_bucket() {
+        let mut virgin = vec![0xff; 4];
+        assert!(merge_new_bits(&mut virgin, &[0, 1, 0, 0]));
+        assert!(!merge_new_bits(&mut virgin, &[0, 1, 0, 0]));
+        // Same edge, a different bucket of hit counts.
+        assert!(merge_new_bits(&mut virgin, &[0, 5, 0, 0]));
+        assert!(!merge_new_bits(&mut virgin, &[0, 6, 0, 0]));
+        // A new edge.
+        assert!(merge_new_bits(&mut virgin, &[0, 0, 0, 200]));
+    }
+
+    #[test]
+    fn shared_map_is_cleared_and_counted() {
+        let mut coverage = Coverage::new().unwrap();
+        coverage.reset();
+        assert!(!coverage.update());
+        assert!(!coverage.saw_any());
+
+        unsafe { *coverage.map.ptr.add(7) = 3 };
+        assert!(coverage.update());
+        assert!(coverage.saw_any());
+        assert_eq!(coverage.covered_edges(), 1);
+
+        coverage.reset();
+        assert!(coverage.map.as_slice().iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn shared_progress_counts_coverage_once() {
+        let mut first = Coverage::new().unwrap();
+        let mut second = Coverage::new().unwrap();
+        second.share_progress_with(&first);
+        first.reset();
+        second.reset();
+
+        unsafe { *first.map.ptr.add(7) = 1 };
+        unsafe { *second.map.ptr.add(7) = 1 };
+        assert!(first.update());
+        assert!(!second.update());
+        assert_eq!(second.covered_edges(), 1);
+        assert_eq!(second.interesting_execs(), 1);
+    }
+}
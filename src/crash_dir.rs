@@ -0,0 +1,152 @@
+//! Saving crashing inputs to a directory, byte-exact, so they can be replayed.
+//!
+//! Every crash is written as two files: the raw input, named after the hash
+//! of its content, and a small `<name>.meta` text file next to it describing
+//! how the target crashed. Other kinds of findings are saved the same way,
+//! like hangs as `hang-<hash>`.
+
+use crate::dedup::{stable_hash, Crash, FindingKind};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How much of the target's stderr is kept in the metadata file.
+const STDERR_EXCERPT_LEN: usize = 2048;
+
+#[derive(Debug, Clone)]
+pub struct CrashDir {
+    dir: PathBuf,
+    /// Details of the whole run (like the fuzzing mode) that are written into
+    /// every metadata file.
+    context: Vec<(String, String)>,
+}
+
+impl CrashDir {
+    /// Use the given directory for crashes, creating it if needed.
+    pub fn create(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            context: Vec::new(),
+        })
+    }
+
+    /// Add a detail that is written into the metadata of every crash.
+    pub fn add_context(&mut self, key: impl Into<String>, value: impl ToString) {
+        self.context.push((key.into(), value.to_string()));
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The path an input of this kind of finding is saved at.
+    pub fn input_path(&self, kind: FindingKind, input: &[u8]) -> PathBuf {
+        self.dir
+            .join(format!("{}-{:016x}", kind.name(), stable_hash(input)))
+    }
+
+    fn meta_path(&self, kind: FindingKind, input: &[u8]) -> PathBuf {
+        self.input_path(kind, input).with_extension("meta")
+    }
+
+    /// Write the crash's input and metadata. Returns the path of the input.
+    pub fn save(&self, crash: &Crash) -> io::Result<PathBuf> {
+        let path = self.input_path(crash.kind, &crash.input);
+        fs::write(&path, &crash.input)?;
+        fs::write(self.meta_path(crash.kind, &crash.input), self.metadata(crash))?;
+        Ok(path)
+    }
+
+    /// Remove a previously saved input and its metadata, like when a smaller
+    /// input is found for the same crash.
+    pub fn remove(&self, kind: FindingKind, input: &[u8]) -> io::Result<()> {
+        fs::remove_file(self.input_path(kind, input))?;
+        fs::remove_file(self.meta_path(kind, input))
+    }
+
+    fn metadata(&self, crash: &Crash) -> String {
+        let mut meta = String::new();
+        for (key, value) in &self.context {
+            let _ = writeln!(meta, "{}: {}", key, value);
+        }
+        let _ = writeln!(meta, "exit: {}", crash.exit);
+        let _ = writeln!(meta, "duration: {:.3}s", crash.duration.as_secs_f64());
+        let _ = writeln!(meta, "size: {}", crash.input.len());
+        let _ = writeln!(meta, "signature: {:016x}", crash.signature.stderr_hash);
+        if let Some(report) = &crash.sanitizer {
+            let _ = writeln!(meta, "sanitizer: {}", report);
+        }
+        let _ = writeln!(meta, "stderr:");
+        meta.push_str(excerpt(&crash.stderr, STDERR_EXCERPT_LEN));
+        meta
+    }
+}
+
+/// Every input file in a directory, sorted by name. Metadata files are
+/// skipped, so this works both for crash directories and for plain
+/// directories of inputs.
+pub fn input_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_none_or(|ext| ext != "meta") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The start of the text, cut at a char boundary.
+fn excerpt(text: &str, max_len: usize) -> &str {
+    if text.len() <= max_len {
+        return text;
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::CrashSignature;
+    use crate::runner::ExitKind;
+    use std::time::Duration;
+
+    #[test]
+    fn saves_input_and_metadata() {
+        let dir = std::env::temp_dir().join(format!("fuzzer-crash-dir-{}", std::process::id()));
+        let mut crash_dir = CrashDir::create(&dir).unwrap();
+        crash_dir.add_context("mode", "Urls");
+        let input = vec![0, 159, 146, 150, b'\n'];
+        let crash = Crash {
+            kind: FindingKind::Crash,
+            input: input.clone(),
+            exit: ExitKind::Signal(11),
+            stderr: "Segmentation fault\n".to_string(),
+            sanitizer: None,
+            signature: CrashSignature::new(ExitKind::Signal(11), ""),
+            duration: Duration::from_millis(5),
+            hits: 1,
+        };
+
+        let path = crash_dir.save(&crash).unwrap();
+        assert_eq!(input_files(&dir).unwrap(), vec![path.clone()]);
+        assert_eq!(fs::read(&path).unwrap(), input);
+        let meta = fs::read_to_string(path.with_extension("meta")).unwrap();
+        assert!(meta.starts_with("mode: Urls\nexit: signal 11\nduration: 0.005s\n"));
+        assert!(meta.ends_with("stderr:\nSegmentation fault\n"));
+
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with("crash-"));
+        crash_dir.remove(crash.kind, &input).unwrap();
+        assert!(!path.exists());
+        fs::remove_dir(&dir).unwrap();
+    }
+}
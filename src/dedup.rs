@@ -0,0 +1,361 @@
+//! Grouping of crashes into buckets, so a bug that is hit thousands of times
+//! is only reported once.
+//!
+//! A bucket is keyed on how the process died, and on a hash of the top frames
+//! found in its stderr. Frames are normalised first, so addresses, pids and
+//! line numbers that change between runs do not split a bucket. For sanitizer
+//! reports, the bug type and the top frames of the parsed report are used.
+
+use crate::runner::ExitKind;
+use crate::sanitizer::{self, SanitizerReport, TOP_FRAMES};
+use std::{collections::HashMap, time::Duration};
+
+/// How many lines from the end of stderr are used for the hash, when there
+/// are no stack frames in it.
+const TAIL_LINES: usize = 2;
+
+/// Identifies the kind of a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CrashSignature {
+    pub exit: ExitKind,
+    pub stderr_hash: u64,
+}
+
+impl CrashSignature {
+    pub fn new(exit: ExitKind, stderr: &str) -> Self {
+        let mut hash = Fnv1a::default();
+        for line in normalised_signature_lines(stderr) {
+            hash.write(line.as_bytes());
+            hash.write(b"\n");
+        }
+        Self {
+            exit,
+            stderr_hash: hash.finish(),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash. Unlike the std hasher, it is stable across builds
+/// and platforms, so hashes can be saved and compared between runs.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv1a::default();
+    hash.write(bytes);
+    hash.finish()
+}
+
+/// Parse a Python traceback frame, `File "/src/x.py", line 5, in parse`, into
+/// its file and function, dropping the line number.
+fn python_frame(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("File \"")?;
+    let (file, rest) = rest.split_once('"')?;
+    let (_, function) = rest.rsplit_once(", in ")?;
+    Some((file, function))
+}
+
+/// Replace every run of digits (and hex numbers) with a single `N`, so
+/// addresses, pids and sizes do not change the hash.
+fn mask_numbers(line: &str) -> String {
+    let mut masked = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let is_hex = c == '0' && chars.peek() == Some(&'x');
+            if is_hex {
+                chars.next();
+            }
+            while chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_digit() || (is_hex && c.is_ascii_hexdigit()))
+            {
+                chars.next();
+            }
+            masked.push('N');
+        } else {
+            masked.push(c);
+        }
+    }
+    masked
+}
+
+/// The lines of stderr that identify the crash, after normalisation.
+fn normalised_signature_lines(stderr: &str) -> Vec<String> {
+    // Sanitizer reports: the bug type and the top frames of the first stack
+    // trace, or where UBSan found the bug if it printed no stack trace.
+    if let Some(report) = sanitizer::parse(stderr) {
+        let mut signature = vec![mask_numbers(&report.bug_type)];
+        match report.location {
+            Some(location) if report.frames.is_empty() => signature.push(location),
+            _ => signature.extend(report.frames),
+        }
+        return signature;
+    }
+
+    // Other stack traces in the same format.
+    let lines: Vec<&str> = stderr.lines().map(str::trim).collect();
+    let mut signature: Vec<String> = lines
+        .iter()
+        .filter_map(|line| sanitizer::frame_function(line))
+        .take(TOP_FRAMES)
+        .map(str::to_string)
+        .collect();
+    if !signature.is_empty() {
+        return signature;
+    }
+
+    // Python tracebacks: the innermost frames are last, and so is the
+    // exception type.
+    let python_frames: Vec<_> = lines.iter().filter_map(|line| python_frame(line)).collect();
+    if !python_frames.is_empty() {
+        let top = python_frames.iter().rev().take(TOP_FRAMES);
+        signature.extend(top.map(|(file, function)| format!("{} {}", file, function)));
+        if let Some(last) = lines.iter().rev().find(|line| !line.is_empty()) {
+            let exception = last.split(':').next().unwrap_or(last);
+            signature.push(exception.to_string());
+        }
+        return signature;
+    }
+
+    // Anything else: the last few lines, masked.
+    let mut tail: Vec<String> = lines
+        .iter()
+        .rev()
+        .filter(|line| !line.is_empty())
+        .take(TAIL_LINES)
+        .map(|line| mask_numbers(line))
+        .collect();
+    tail.reverse();
+    tail
+}
+
+/// The kinds of bugs that are reported, each kept and saved separately.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    #[default]
+    Crash,
+    /// The target did not finish, even with a longer timeout.
+    Hang,
+    /// The target ran out of memory, or was killed for using too much.
+    OutOfMemory,
+}
+
+impl FindingKind {
+    /// What findings of this kind are called in reports and file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FindingKind::Crash => "crash",
+            FindingKind::Hang => "hang",
+            FindingKind::OutOfMemory => "oom",
+        }
+    }
+}
+
+/// An input that made the target crash, representing its whole bucket. Hangs
+/// and out-of-memory findings are kept the same way, with their own `kind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crash {
+    pub kind: FindingKind,
+    /// The smallest input found that crashes this way.
+    pub input: Vec<u8>,
+    pub exit: ExitKind,
+    /// What the target wrote to stderr when it crashed on `input`.
+    pub stderr: String,
+    /// What a sanitizer reported about the crash, if the target was built
+    /// with one.
+    pub sanitizer: Option<SanitizerReport>,
+    pub signature: CrashSignature,
+    /// How long the target ran on `input` before crashing.
+    pub duration: Duration,
+    /// How many times a crash in this bucket was seen.
+    pub hits: u64,
+}
+
+/// What happened when a crash was added to the buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bucketed {
+    /// This is the first crash of its kind.
+    New,
+    /// The bucket already existed, but this input is smaller, so it replaced
+    /// the bucket's previous input.
+    Smaller { previous_input: Vec<u8> },
+    /// The bucket already has an input that is at least as small.
+    Duplicate,
+}
+
+/// Crashes (or other findings of one kind), grouped by their signature.
+#[derive(Debug, Default, Clone)]
+pub struct CrashBuckets {
+    kind: FindingKind,
+    by_signature: HashMap<CrashSignature, usize>,
+    crashes: Vec<Crash>,
+}
+
+impl CrashBuckets {
+    pub fn new(kind: FindingKind) -> Self {
+        Self {
+            kind,
+            ..Self::default()
+        }
+    }
+
+    /// Add a crash to its bucket. Returns what happened, and the crash that
+    /// now represents the bucket.
+    pub fn add(
+        &mut self,
+        input: Vec<u8>,
+        exit: ExitKind,
+        stderr: String,
+        duration: Duration,
+    ) -> (Bucketed, &Crash) {
+        let signature = CrashSignature::new(exit, &stderr);
+        let Some(&index) = self.by_signature.get(&signature) else {
+            self.by_signature.insert(signature, self.crashes.len());
+            self.crashes.push(Crash {
+                kind: self.kind,
+                input,
+                exit,
+                sanitizer: sanitizer::parse(&stderr),
+                stderr,
+                signature,
+                duration,
+                hits: 1,
+            });
+            return (Bucketed::New, self.crashes.last().unwrap());
+        };
+        let crash = &mut self.crashes[index];
+        crash.hits += 1;
+        let bucketed = if input.len() < crash.input.len() {
+            crash.sanitizer = sanitizer::parse(&stderr);
+            crash.stderr = stderr;
+            crash.duration = duration;
+            Bucketed::Smaller {
+                previous_input: std::mem::replace(&mut crash.input, input),
+            }
+        } else {
+            Bucketed::Duplicate
+        };
+        (bucketed, crash)
+    }
+
+    /// The number of crashes seen, including duplicates.
+    pub fn total_hits(&self) -> u64 {
+        self.crashes.iter().map(|crash| crash.hits).sum()
+    }
+
+    /// The number of buckets, that is of distinct crashes.
+    pub fn buckets(&self) -> usize {
+        self.crashes.len()
+    }
+
+    /// One crash per bucket, in the order the buckets were found.
+    pub fn into_crashes(self) -> Vec<Crash> {
+        self.crashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASAN_REPORT: &str = "\
+=================================================================
+==31337==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x4f5a3c bp 0x7ffd sp 0x7ffc
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x4f5a3c in parse_host /src/url.c:42:9
+    #1 0x4f6b10 in parse_url /src/url.c:97:5
+    #2 0x4f7000 in main /src/main.c:12:3
+    #3 0x7f00aa in __libc_start_main
+";
+
+    #[test]
+    fn same_bug_different_addresses_same_signature() {
+        let other_run = ASAN_REPORT
+            .replace("31337", "4242")
+            .replace("0x4f5a3c", "0x55aa3c")
+            .replace(":42:9", ":43:9");
+        let exit = ExitKind::Signal(6);
+        assert_eq!(
+            CrashSignature::new(exit, ASAN_REPORT),
+            CrashSignature::new(exit, &other_run)
+        );
+    }
+
+    #[test]
+    fn different_frames_or_exit_different_signature() {
+        let other_bug = ASAN_REPORT.replace("parse_host", "parse_port");
+        let exit = ExitKind::Signal(6);
+        assert_ne!(
+            CrashSignature::new(exit, ASAN_REPORT),
+            CrashSignature::new(exit, &other_bug)
+        );
+        assert_ne!(
+            CrashSignature::new(exit, ASAN_REPORT),
+            CrashSignature::new(ExitKind::Code(1), ASAN_REPORT)
+        );
+    }
+
+    #[test]
+    fn python_traceback_ignores_line_numbers_and_message() {
+        let traceback = |line, message| {
+            format!(
+                "Traceback (most recent call last):\n  File \"/t/x.py\", line {}, in <module>\n    main()\nValueError: {}\n",
+                line, message
+            )
+        };
+        let exit = ExitKind::Code(1);
+        assert_eq!(
+            CrashSignature::new(exit, &traceback(5, "bad 'a'")),
+            CrashSignature::new(exit, &traceback(6, "bad 'b'"))
+        );
+        assert_ne!(
+            CrashSignature::new(exit, &traceback(5, "x")),
+            CrashSignature::new(exit, &traceback(5, "x").replace("ValueError", "KeyError"))
+        );
+    }
+
+    #[test]
+    fn buckets_keep_the_smallest_input() {
+        let mut buckets = CrashBuckets::default();
+        let mut add = |input: &[u8], exit| {
+            let (bucketed, _) = buckets.add(input.to_vec(), exit, String::new(), Duration::ZERO);
+            bucketed
+        };
+        let exit = ExitKind::Signal(11);
+        assert_eq!(add(b"aaaa", exit), Bucketed::New);
+        assert_eq!(add(b"aaaaaa", exit), Bucketed::Duplicate);
+        assert_eq!(
+            add(b"aa", exit),
+            Bucketed::Smaller {
+                previous_input: b"aaaa".to_vec()
+            }
+        );
+        assert_eq!(add(b"a", ExitKind::Code(1)), Bucketed::New);
+
+        assert_eq!(buckets.total_hits(), 4);
+        let crashes = buckets.into_crashes();
+        assert_eq!(crashes.len(), 2);
+        assert_eq!(crashes[0].input, b"aa");
+        assert_eq!(crashes[0].hits, 3);
+    }
+}
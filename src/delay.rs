@@ -32,10 +32,16 @@ pub struct Delayer<A: Action> {
 
 #[derive(Debug)]
 struct State<A: Action> {
-    request: Mutex<Option<Request<A>>>,
-    request_arrived: Condvar,
-    cancel: Mutex<bool>,
-    cancel_set_true: Condvar,
+    inner: Mutex<Inner<A>>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct Inner<A: Action> {
+    /// A request that was set, but the worker has not started waiting on yet.
+    request: Option<Request<A>>,
+    /// Set when the request the worker is waiting on is cancelled.
+    cancel: bool,
 }
 
 #[derive(Debug)]
@@ -49,15 +55,17 @@ impl<T> Action for T where T: FnOnce() + Send + 'static {}
 
 impl<A: Action> Delayer<A> {
     fn wait_and_pop_request(state: &State<A>) -> Request<A> {
-        let request = state.request.lock().unwrap();
-        let mut request = state.request_arrived.wait_while(request, |request| request.is_none()).unwrap();
-        request.take().expect("This must have a value based on the condition above")
+        let inner = state.inner.lock().unwrap();
+        let mut inner = state.changed.wait_while(inner, |inner| inner.request.is_none()).unwrap();
+        // A cancel that arrived while we were idle belongs to an older request.
+        inner.cancel = false;
+        inner.request.take().expect("This must have a value based on the condition above")
     }
 
     fn wait_timeout_or_cancel(state: &State<A>, delay: Duration) -> WaitTimeoutResult {
-        let cancel = state.cancel.lock().unwrap();
-        let (mut cancel, timeout_result) = state.cancel_set_true.wait_timeout_while(cancel, delay, |cancel| !*cancel).unwrap();
-        *cancel = false;
+        let inner = state.inner.lock().unwrap();
+        let (mut inner, timeout_result) = state.changed.wait_timeout_while(inner, delay, |inner| !inner.cancel).unwrap();
+        inner.cancel = false;
         timeout_result
     }
 
@@ -75,23 +83,30 @@ impl<A: Action> Delayer<A> {
 
     pub fn new() -> Self {
         let state = Arc::new(State {
-            request: Mutex::new(None),
-            request_arrived: Condvar::new(),
-            cancel: Mutex::new(false),
-            cancel_set_true: Condvar::new(),
+            inner: Mutex::new(Inner {
+                request: None,
+                cancel: false,
+            }),
+            changed: Condvar::new(),
         });
         Self::start_worker(state.clone());
         Self { state }
     }
 
+    /// Cancel the last request that was set, if its action was not called yet.
     pub fn cancel(&self) {
-        let mut cancel = self.state.cancel.lock().unwrap();
-        *cancel = true;
-        self.state.cancel_set_true.notify_all();
+        let mut inner = self.state.inner.lock().unwrap();
+        // If the worker did not get to the request yet, just drop it.
+        if inner.request.take().is_none() {
+            inner.cancel = true;
+        }
+        self.state.changed.notify_all();
     }
 
     pub fn set(&self, delay: Duration, action: A) {
-        self.state.request.lock().unwrap().replace(Request { delay, action });
+        let mut inner = self.state.inner.lock().unwrap();
+        inner.request.replace(Request { delay, action });
+        self.state.changed.notify_all();
     }
 }
 
@@ -120,4 +135,20 @@ mod tests {
         was_called.wait().until_raised();
         assert!(start.elapsed() >= duration);
     }
+
+    #[test]
+    fn test_delayer_after_cancel_and_idle() {
+        let delayer = Delayer::new();
+        // Let the worker go idle, then cancel a request and set a new one.
+        std::thread::sleep(Duration::from_millis(10));
+        delayer.set(Duration::from_secs(10), Box::new(|| ()) as Box<dyn FnOnce() + Send>);
+        delayer.cancel();
+        std::thread::sleep(Duration::from_millis(10));
+        delayer.cancel();
+
+        let was_called = WaitableFlag::default();
+        delayer.set(Duration::from_millis(10), Box::new(was_called.get_raise()));
+        was_called.wait().until_raised_timeout(Duration::from_secs(1));
+        assert!(was_called.is_raised());
+    }
 }
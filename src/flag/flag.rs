@@ -15,6 +15,10 @@ impl Flag {
         }
     }
 
+    pub fn raise(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
     pub fn is_raised(&self) -> bool {
         self.0.load(Ordering::Relaxed)
     }
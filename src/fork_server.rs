@@ -0,0 +1,247 @@
+//! Running the target through an AFL-compatible fork server, for targets
+//! linked with `resources/forkserver/forkserver_runtime.c`.
+//!
+//! The target is started once, and stops before main. For every input, it
+//! forks a fresh copy of itself that runs main, which saves the cost of an
+//! exec and of the dynamic linker for every input. We talk to it over two
+//! pipes, the control pipe (its fd 198) and the status pipe (its fd 199); see
+//! the runtime for the protocol.
+//!
+//! Every copy inherits the fork server's stdin and stderr, which are scratch
+//! files that we rewrite before every input (see the `child_files` module).
+//! Targets that take the input from an `@@` file get the same file every time.
+//!
+//! Targets in persistent mode run many inputs in one copy, which stops itself
+//! after every input and is continued for the next one. They read the input
+//! from shared memory (a `u32` length, then the bytes), which we fill in
+//! before every input too. Each input is a run of its own for us, with its
+//! own timeout.
+
+use crate::child_files::{self, read_written, rewrite, scratch_file};
+use crate::coverage::SharedMap;
+use crate::target::{InputDelivery, InputFile, Target};
+use crate::target_process::{Running, TargetProcess};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::{CommandExt, ExitStatusExt},
+    },
+    process::{ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Must match `CONTROL_FD` in the runtime.
+pub const CONTROL_FD: RawFd = 198;
+/// Must match `STATUS_FD` in the runtime.
+pub const STATUS_FD: RawFd = 199;
+/// How long the fork server may take to start, in single execution
+/// timeouts. Starting includes loading the target, which can take a while
+/// for sanitized builds.
+const START_TIMEOUT_FACTOR: u32 = 10;
+/// The environment variable the runtime reads the input shared memory id
+/// from. Must match `INPUT_SHM_ENV_VAR` in the runtime.
+pub const INPUT_SHM_ENV_VAR: &str = "FUZZER_INPUT_SHM_ID";
+/// How much of an input fits in shared memory. Must match `MAX_INPUT_LEN` in
+/// the runtime.
+pub const MAX_INPUT_LEN: usize = 1 << 20;
+const LEN_SIZE: usize = size_of::<u32>();
+
+pub(crate) struct ForkServer {
+    server: TargetProcess,
+    control: File,
+    status: Arc<File>,
+    /// The stdin of every copy, for targets that read the input from it.
+    stdin: Option<File>,
+    /// The `@@` file, for targets that read the input from a file.
+    input_file: Option<InputFile>,
+    /// The input of copies in persistent mode.
+    shared_input: SharedMap,
+    stderr: File,
+}
+
+impl ForkServer {
+    /// Start the target as a fork server. Returns `None` if it does not speak
+    /// the protocol, like when it was not linked with the runtime.
+    pub fn start(target: &Target, timeout: Duration) -> io::Result<Option<Self>> {
+        let delivery = target.input_delivery();
+        if !matches!(delivery, InputDelivery::Stdin | InputDelivery::File) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "inputs passed in arguments or environment variables need a new process every time",
+            ));
+        }
+        let mut input_file = None;
+        let mut command = target.command(b"", &mut input_file)?;
+        let shared_input = SharedMap::new(LEN_SIZE + MAX_INPUT_LEN)?;
+        command.env(INPUT_SHM_ENV_VAR, shared_input.id().to_string());
+        let (control_read, control) = pipe()?;
+        let (status, status_write) = pipe()?;
+        let stderr = scratch_file()?;
+        let stdin = match delivery {
+            InputDelivery::Stdin => Some(scratch_file()?),
+            _ => None,
+        };
+
+        let (control_fd, status_fd) = (control_read.as_raw_fd(), status_write.as_raw_fd());
+        // Safety: dup2 is async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::dup2(control_fd, CONTROL_FD) < 0 || libc::dup2(status_fd, STATUS_FD) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            })
+        };
+        let stdin_stdio = match &stdin {
+            Some(file) => Stdio::from(file.try_clone()?),
+            None => Stdio::null(),
+        };
+        command
+            .stdin(stdin_stdio)
+            .stdout(Stdio::null())
+            .stderr(Stdio::from(stderr.try_clone()?));
+        let server = TargetProcess::spawn(&mut command)?;
+        // Only the fork server may hold these ends, so we notice when it dies.
+        drop((control_read, status_write));
+
+        let fork_server = Self {
+            server,
+            control: File::from(control),
+            status: Arc::new(File::from(status)),
+            stdin,
+            input_file,
+            shared_input,
+            stderr,
+        };
+        if !is_readable(&fork_server.status, Some(timeout * START_TIMEOUT_FACTOR))? {
+            return Ok(None);
+        }
+        // A target without the runtime runs to the end, and closes the pipe.
+        match read_u32(&fork_server.status) {
+            Ok(_hello) => Ok(Some(fork_server)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Have the fork server fork a copy of the target that runs with this
+    /// input. Only one copy may run at a time.
+    pub fn run(&mut self, input: &[u8]) -> io::Result<ForkedChild> {
+        if let Some(stdin) = &mut self.stdin {
+            rewrite(stdin, input)?;
+        }
+        if let Some(input_file) = &self.input_file {
+            input_file.write(input)?;
+        }
+        let len = input.len().min(MAX_INPUT_LEN);
+        let shared_input = self.shared_input.as_mut_slice();
+        shared_input[..LEN_SIZE].copy_from_slice(&(len as u32).to_ne_bytes());
+        shared_input[LEN_SIZE..LEN_SIZE + len].copy_from_slice(&input[..len]);
+        rewrite(&mut self.stderr, b"")?;
+        self.control.write_all(&0u32.to_ne_bytes())?;
+        let pid = read_u32(&self.status)? as libc::pid_t;
+        Ok(ForkedChild {
+            pid,
+            status: self.status.clone(),
+            exited: Mutex::new(false),
+        })
+    }
+
+    /// What the last copy wrote to stderr, up to `max_len` bytes.
+    pub fn stderr(&self, max_len: u64) -> io::Result<Vec<u8>> {
+        read_written(&self.stderr, max_len)
+    }
+}
+
+impl Drop for ForkServer {
+    fn drop(&mut self) {
+        self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+/// A copy of the target forked by the fork server.
+pub(crate) struct ForkedChild {
+    pid: libc::pid_t,
+    status: Arc<File>,
+    /// Whether the fork server reported the copy's exit, after which the pid
+    /// may be reused, or that it stopped after the input in persistent mode.
+    exited: Mutex<bool>,
+}
+
+impl Running for ForkedChild {
+    fn kill(&self) {
+        let exited = self.exited.lock().unwrap();
+        if !*exited {
+            // The fork server puts every copy in a process group of its own.
+            unsafe { libc::killpg(self.pid, libc::SIGKILL) };
+        }
+    }
+
+    /// Wait until the fork server reports the copy's status, then read it
+    /// and mark the copy as exited under the same lock, so `kill` cannot send
+    /// a signal to its pid in between.
+    fn wait(&self) -> io::Result<ExitStatus> {
+        is_readable(&self.status, None)?;
+        let mut exited = self.exited.lock().unwrap();
+        let status = read_u32(&self.status)? as i32;
+        *exited = true;
+        if libc::WIFSTOPPED(status) {
+            // A copy in persistent mode is done with the input, and waits
+            // for the next one.
+            return Ok(ExitStatus::from_raw(0));
+        }
+        Ok(ExitStatus::from_raw(status))
+    }
+}
+
+/// A pipe out of the way of the fds the fork server is given, so moving the
+/// other end there does not close them.
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let (read, write) = child_files::pipe()?;
+    Ok((above_server_fds(read)?, above_server_fds(write)?))
+}
+
+fn above_server_fds(fd: OwnedFd) -> io::Result<OwnedFd> {
+    let moved = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, STATUS_FD + 1) };
+    if moved < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(moved) })
+}
+
+fn read_u32(mut file: &File) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_ne_bytes(bytes))
+}
+
+/// Wait until there is something to read from the file, or it is closed.
+/// Returns false if the timeout expires first. Without a timeout, waits as
+/// long as it takes.
+pub(crate) fn is_readable(file: &File, timeout: Option<Duration>) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = match timeout {
+        Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+        None => -1,
+    };
+    loop {
+        match unsafe { libc::poll(&mut poll_fd, 1, millis) } {
+            0 => return Ok(false),
+            result if result > 0 => return Ok(true),
+            _ => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
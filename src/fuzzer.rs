@@ -1,5 +1,58 @@
+use crate::runner::ExitKind;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// What happened when the target ran on an input the fuzzer generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation<'a> {
+    pub input: &'a [u8],
+    pub exit: ExitKind,
+    /// How long the target ran on the input.
+    pub duration: Duration,
+    /// Whether the input reached code no earlier input reached, or `None` if
+    /// coverage is not collected.
+    pub new_coverage: Option<bool>,
+    /// Whether the input crashed the target in a way not seen before.
+    pub new_crash: bool,
+}
+
+/// What a fuzzer is doing at the moment, for status displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Going through a list of fixed inputs, like a corpus. `index` inputs of
+    /// the `total` were generated so far.
+    Predefined { index: usize, total: usize },
+    /// Generating new random inputs.
+    Random,
+    /// Mutating an earlier input.
+    Mutate,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Phase::Predefined { index, total } => write!(f, "predefined {}/{}", index, total),
+            Phase::Random => write!(f, "random"),
+            Phase::Mutate => write!(f, "mutate"),
+        }
+    }
+}
+
 pub trait Fuzzer {
     fn generate_input(&mut self) -> Vec<u8>;
+
+    /// Called after the target ran on the last generated input, so the fuzzer
+    /// can learn from it. Inputs that could not be delivered to the target at
+    /// all (see `Delivered::TooLarge`) never ran, so they are not observed.
+    /// Does nothing by default.
+    fn observe(&mut self, _observation: &Observation) {}
+
+    /// What the fuzzer is doing, if it can tell. `None` by default.
+    fn phase(&self) -> Option<Phase> {
+        None
+    }
 }
 
 pub struct DefaultFuzzer;
@@ -10,4 +63,3 @@ impl Fuzzer for DefaultFuzzer {
         "default input".as_bytes().to_vec()
     }
 }
-
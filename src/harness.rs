@@ -0,0 +1,355 @@
+//! In-process fuzzing of libFuzzer harnesses: shared libraries that export
+//! `LLVMFuzzerTestOneInput` (and maybe `LLVMFuzzerInitialize`).
+//!
+//! The library is loaded into our process once, but the harness is only ever
+//! called in a supervised child we fork, so a crash only takes the child
+//! down. The child gets the inputs over a pipe, calls the harness with each
+//! of them, and writes a byte back when the harness returns. When it dies, the
+//! input it was running is what killed it, and the next input gets a new
+//! child.
+//!
+//! Like with the fork server, the child's stderr is a scratch file that is
+//! rewritten before every input (see the `child_files` module).
+
+use crate::child_files::{pipe, read_written, rewrite, scratch_file};
+use crate::target::{limit_memory, Target};
+use crate::target_process::Running;
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fs::File,
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::{ffi::OsStrExt, process::ExitStatusExt},
+    },
+    path::Path,
+    process::ExitStatus,
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+type TestOneInput = unsafe extern "C" fn(*const u8, usize) -> c_int;
+type Initialize = unsafe extern "C" fn(*mut c_int, *mut *mut *mut c_char) -> c_int;
+
+/// Where the child finds its pipes, after everything else is closed.
+const INPUT_FD: RawFd = 3;
+const RESULT_FD: RawFd = 4;
+
+/// The entry points of a loaded harness library.
+#[derive(Clone, Copy)]
+struct Harness {
+    test_one_input: TestOneInput,
+    initialize: Option<Initialize>,
+}
+
+impl Harness {
+    /// Load the library. It is never unloaded, as children forked from us
+    /// may still be running its code.
+    fn load(library: &Path) -> io::Result<Self> {
+        let path = CString::new(library.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(io::Error::other(dl_error()));
+        }
+        let symbol = |name: &CStr| unsafe { libc::dlsym(handle, name.as_ptr()) };
+        let test_one_input = symbol(c"LLVMFuzzerTestOneInput");
+        if test_one_input.is_null() {
+            return Err(io::Error::other(format!(
+                "{} does not export LLVMFuzzerTestOneInput",
+                library.display()
+            )));
+        }
+        let initialize = symbol(c"LLVMFuzzerInitialize");
+        // Safety: these are the signatures libFuzzer harnesses have.
+        unsafe {
+            Ok(Self {
+                test_one_input: mem::transmute::<*mut c_void, TestOneInput>(test_one_input),
+                initialize: (!initialize.is_null())
+                    .then(|| mem::transmute::<*mut c_void, Initialize>(initialize)),
+            })
+        }
+    }
+}
+
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        return "could not load the library".to_string();
+    }
+    unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+}
+
+/// Runs inputs through a harness in a supervised child, starting a new child
+/// whenever the last one died.
+pub(crate) struct Supervisor {
+    harness: Harness,
+    mem_limit: Option<u64>,
+    child: Option<Child>,
+    stderr: File,
+}
+
+/// The supervised child, as long as it lives.
+struct Child {
+    pid: libc::pid_t,
+    input: File,
+    result: Arc<File>,
+    state: Arc<Mutex<ChildState>>,
+}
+
+/// What became of the child, shared with the runs of its inputs.
+#[derive(Default)]
+struct ChildState {
+    /// Set once it was sent SIGKILL. The harness may have returned just
+    /// before, but the child is dying all the same.
+    killed: bool,
+    /// Set once the child was reaped, after it died.
+    reaped: bool,
+}
+
+impl Supervisor {
+    /// Load the target's executable as a harness library. The harness runs in
+    /// a copy of our process, so it cannot be given arguments or an
+    /// environment of its own.
+    pub fn new(target: &Target) -> io::Result<Self> {
+        if !target.args.is_empty()
+            || target.input_env.is_some()
+            || !target.env.is_empty()
+            || target.clear_env
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "harnesses take no arguments or environment",
+            ));
+        }
+        Ok(Self {
+            harness: Harness::load(&target.executable)?,
+            mem_limit: target.mem_limit,
+            child: None,
+            stderr: scratch_file()?,
+        })
+    }
+
+    /// Have the child call the harness with this input.
+    pub fn run(&mut self, input: &[u8]) -> io::Result<HarnessRun> {
+        if let Some(child) = &self.child {
+            let mut state = child.state.lock().unwrap();
+            // Killed after its last input finished, so nobody reaped it.
+            if state.killed && !state.reaped {
+                reap(child.pid, &mut state)?;
+            }
+            if state.reaped {
+                drop(state);
+                self.child = None;
+            }
+        }
+        if self.child.is_none() {
+            self.child = Some(self.fork()?);
+        }
+        rewrite(&mut self.stderr, b"")?;
+        let child = self.child.as_mut().unwrap();
+        let len = u32::try_from(input.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the input is too large"))?;
+        let sent = child
+            .input
+            .write_all(&len.to_ne_bytes())
+            .and_then(|()| child.input.write_all(input));
+        match sent {
+            // The child died, which the run will tell.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            result => result?,
+        }
+        Ok(HarnessRun {
+            pid: child.pid,
+            result: child.result.clone(),
+            state: child.state.clone(),
+        })
+    }
+
+    /// What the child wrote to stderr during the last input, up to `max_len`
+    /// bytes.
+    pub fn stderr(&self, max_len: u64) -> io::Result<Vec<u8>> {
+        read_written(&self.stderr, max_len)
+    }
+
+    fn fork(&self) -> io::Result<Child> {
+        let (input_read, input_write) = pipe()?;
+        let (result_read, result_write) = pipe()?;
+        let null = File::options().write(true).open("/dev/null")?;
+        match unsafe { libc::fork() } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                // The child is a copy of a process with many threads, with
+                // only this one left. Locks the others held when we forked
+                // stay locked forever, so it only makes system calls until
+                // `supervise` takes over. From there it allocates, and runs
+                // the harness' code, which is fine with glibc: its fork()
+                // takes the malloc locks and releases them in the child, and
+                // sanitizer runtimes do the same for their allocators. Other
+                // locks of ours, like Rust's stdout, must not be taken there.
+                unsafe {
+                    libc::setpgid(0, 0);
+                    let input = libc::fcntl(input_read.as_raw_fd(), libc::F_DUPFD, 10);
+                    let result = libc::fcntl(result_write.as_raw_fd(), libc::F_DUPFD, 10);
+                    libc::dup2(null.as_raw_fd(), 1);
+                    libc::dup2(self.stderr.as_raw_fd(), 2);
+                    libc::dup2(input, INPUT_FD);
+                    libc::dup2(result, RESULT_FD);
+                    // Other children must see their pipes close when their
+                    // process dies, so we must not hold on to them.
+                    libc::syscall(libc::SYS_close_range, RESULT_FD + 1, c_int::MAX, 0);
+                    let mut signals: libc::sigset_t = mem::zeroed();
+                    libc::sigemptyset(&mut signals);
+                    libc::pthread_sigmask(libc::SIG_SETMASK, &signals, ptr::null_mut());
+                }
+                supervise(self.harness, self.mem_limit)
+            }
+            pid => Ok(Child {
+                pid,
+                input: File::from(input_write),
+                result: Arc::new(File::from(result_read)),
+                state: Arc::default(),
+            }),
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            let run = HarnessRun {
+                pid: child.pid,
+                result: child.result,
+                state: child.state,
+            };
+            run.kill();
+            let _ = run.wait();
+        }
+    }
+}
+
+/// The child's loop: call the harness with every input it gets. Runs right
+/// after fork(), so it must not take locks our other threads may have held
+/// (see `Supervisor::fork`).
+fn supervise(harness: Harness, mem_limit: Option<u64>) -> ! {
+    if let Some(bytes) = mem_limit {
+        let _ = limit_memory(bytes);
+    }
+    if let Some(initialize) = harness.initialize {
+        let mut name = *b"fuzzer\0";
+        let mut args = [name.as_mut_ptr().cast::<c_char>(), ptr::null_mut()];
+        let mut argc: c_int = 1;
+        let mut argv = args.as_mut_ptr();
+        unsafe { initialize(&mut argc, &mut argv) };
+    }
+    loop {
+        let mut len = [0; 4];
+        if !read_exact(INPUT_FD, &mut len) {
+            unsafe { libc::_exit(0) };
+        }
+        // A buffer of exactly the input's size, so sanitizers catch reads
+        // past its end.
+        let mut input = vec![0; u32::from_ne_bytes(len) as usize];
+        if !read_exact(INPUT_FD, &mut input) {
+            unsafe { libc::_exit(0) };
+        }
+        unsafe { (harness.test_one_input)(input.as_ptr(), input.len()) };
+        drop(input);
+        if unsafe { libc::write(RESULT_FD, [0u8].as_ptr().cast(), 1) } != 1 {
+            unsafe { libc::_exit(0) };
+        }
+    }
+}
+
+fn read_exact(fd: RawFd, mut buffer: &mut [u8]) -> bool {
+    while !buffer.is_empty() {
+        match unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) } {
+            read if read > 0 => buffer = &mut buffer[read as usize..],
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => (),
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// One input running in the supervised child.
+pub(crate) struct HarnessRun {
+    pid: libc::pid_t,
+    result: Arc<File>,
+    state: Arc<Mutex<ChildState>>,
+}
+
+impl Running for HarnessRun {
+    fn kill(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !state.reaped {
+            unsafe { libc::kill(self.pid, libc::SIGKILL) };
+            state.killed = true;
+        }
+    }
+
+    /// Wait for the harness to return, which counts as a clean exit, or for
+    /// the child to die. A child that was killed is reaped either way, so the
+    /// next input never goes to a dead child.
+    fn wait(&self) -> io::Result<ExitStatus> {
+        let mut done = [0];
+        let returned = match (&*self.result).read(&mut done) {
+            Ok(read) => read == 1,
+            Err(e) => return Err(e),
+        };
+        let mut state = self.state.lock().unwrap();
+        if returned && !state.killed {
+            return Ok(ExitStatus::from_raw(0));
+        }
+        let status = reap(self.pid, &mut state)?;
+        Ok(if returned { ExitStatus::from_raw(0) } else { status })
+    }
+}
+
+/// Wait for the dead or dying child, and mark it as reaped.
+fn reap(pid: libc::pid_t, state: &mut ChildState) -> io::Result<ExitStatus> {
+    let mut status = 0;
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, 0) } >= 0 {
+            break;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    state.reaped = true;
+    Ok(ExitStatus::from_raw(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fork_server::is_readable;
+
+    extern "C" fn returns_at_once(_data: *const u8, _size: usize) -> c_int {
+        0
+    }
+
+    #[test]
+    fn children_killed_after_the_harness_returned_are_replaced() {
+        let mut supervisor = Supervisor {
+            harness: Harness {
+                test_one_input: returns_at_once,
+                initialize: None,
+            },
+            mem_limit: None,
+            child: None,
+            stderr: scratch_file().unwrap(),
+        };
+        let run = supervisor.run(b"first").unwrap();
+        // The harness returned, and a timeout fires before we read that.
+        is_readable(&run.result, None).unwrap();
+        run.kill();
+        assert!(run.wait().unwrap().success());
+
+        let run = supervisor.run(b"second").unwrap();
+        assert!(run.wait().unwrap().success());
+    }
+}
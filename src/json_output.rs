@@ -0,0 +1,380 @@
+//! Machine-readable output of a fuzzing run, for scripts like the benchmarks.
+//!
+//! The statistics file holds one JSON object, replaced as a whole every time
+//! it is written. The event log holds one JSON object per line. Every object
+//! has a `"version"` field, `FORMAT_VERSION`, which changes when fields are
+//! removed or change their meaning (new fields may be added any time). The
+//! fields are documented in the README.
+
+use fuzzer::{Crash, Event, Phase, Status};
+use std::{
+    ffi::OsString,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How often the statistics file is rewritten.
+pub const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+trait ToJson {
+    fn write_json(&self, out: &mut String);
+}
+
+impl ToJson for str {
+    fn write_json(&self, out: &mut String) {
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c < ' ' => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn write_json(&self, out: &mut String) {
+        (**self).write_json(out);
+    }
+}
+
+impl ToJson for String {
+    fn write_json(&self, out: &mut String) {
+        self.as_str().write_json(out);
+    }
+}
+
+impl ToJson for bool {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for u32 {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for u64 {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for usize {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for f64 {
+    fn write_json(&self, out: &mut String) {
+        if self.is_finite() {
+            let _ = write!(out, "{:.3}", self);
+        } else {
+            out.push_str("null");
+        }
+    }
+}
+
+/// Durations are in seconds.
+impl ToJson for Duration {
+    fn write_json(&self, out: &mut String) {
+        self.as_secs_f64().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+impl ToJson for Object {
+    fn write_json(&self, out: &mut String) {
+        out.push_str(&self.0);
+        out.push('}');
+    }
+}
+
+/// A JSON object, built one field at a time.
+struct Object(String);
+
+impl Object {
+    fn new() -> Self {
+        Self(String::from("{"))
+    }
+
+    /// An object starting with the format version.
+    fn versioned() -> Self {
+        Self::new().field("version", FORMAT_VERSION)
+    }
+
+    fn field(mut self, name: &str, value: impl ToJson) -> Self {
+        if self.0.len() > 1 {
+            self.0.push(',');
+        }
+        name.write_json(&mut self.0);
+        self.0.push(':');
+        value.write_json(&mut self.0);
+        self
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+}
+
+/// Bytes as a string of lowercase hex digits, as inputs may not be text.
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
+
+fn phase(phase: Phase) -> Object {
+    match phase {
+        Phase::Predefined { index, total } => Object::new()
+            .field("name", "predefined")
+            .field("index", index)
+            .field("total", total),
+        Phase::Random => Object::new().field("name", "random"),
+        Phase::Mutate => Object::new().field("name", "mutate"),
+    }
+}
+
+fn path(path: &Path) -> String {
+    path.display().to_string()
+}
+
+/// Keeps the statistics of a run in a file, for scripts to poll.
+pub struct StatsFile {
+    path: PathBuf,
+    seed: u64,
+    jobs: usize,
+    /// The elapsed time and executions when the file was last written, for
+    /// the recent rate of executions.
+    last: Option<(Duration, u64)>,
+    failed: bool,
+}
+
+impl StatsFile {
+    pub fn new(path: PathBuf, seed: u64, jobs: usize) -> Self {
+        Self {
+            path,
+            seed,
+            jobs,
+            last: None,
+            failed: false,
+        }
+    }
+
+    /// Write the status, replacing the file at once so readers never see it
+    /// half written.
+    pub fn update(&mut self, status: &Status) {
+        let (last_elapsed, last_execs) = self.last.unwrap_or_default();
+        let interval = status.elapsed.saturating_sub(last_elapsed).as_secs_f64();
+        let recent_execs_per_sec = if interval > 0.0 {
+            (status.execs - last_execs) as f64 / interval
+        } else {
+            status.execs_per_sec()
+        };
+        self.last = Some((status.elapsed, status.execs));
+        let json = Object::versioned()
+            .field("elapsed", status.elapsed)
+            .field("execs", status.execs)
+            .field("execs_per_sec", status.execs_per_sec())
+            .field("recent_execs_per_sec", recent_execs_per_sec)
+            .field("total_crashes", status.total_crashes)
+            .field("unique_crashes", status.unique_crashes)
+            .field("timeouts", status.timeouts)
+            .field("total_hangs", status.total_hangs)
+            .field("unique_hangs", status.unique_hangs)
+            .field("total_out_of_memory", status.total_out_of_memory)
+            .field("unique_out_of_memory", status.unique_out_of_memory)
+            .field("phase", status.phase.map(phase))
+            .field("since_last_finding", status.since_last_finding)
+            .field("seed", self.seed)
+            .field("jobs", self.jobs)
+            .field("done", status.done)
+            .finish();
+
+        let mut temporary = OsString::from(&self.path);
+        temporary.push(".tmp");
+        let written =
+            fs::write(&temporary, json + "\n").and_then(|()| fs::rename(&temporary, &self.path));
+        if let Err(e) = written {
+            // Once is enough, not every second.
+            if !self.failed {
+                eprintln!("Could not write statistics to {:?}: {}", self.path, e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+/// Logs the events of a run as JSON lines.
+pub struct EventLog {
+    out: BufWriter<File>,
+    path: PathBuf,
+    /// What the `started` event tells besides the workers.
+    mode: String,
+    seed: u64,
+    start: Instant,
+    failed: bool,
+}
+
+impl EventLog {
+    pub fn create(path: PathBuf, mode: String, seed: u64) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(&path)?),
+            path,
+            mode,
+            seed,
+            start: Instant::now(),
+            failed: false,
+        })
+    }
+
+    pub fn log(&mut self, event: &Event) {
+        if let Event::Started { .. } = event {
+            self.start = Instant::now();
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let common = |name: &str| {
+            Object::versioned()
+                .field("event", name)
+                .field("time", time)
+                .field("elapsed", self.start.elapsed())
+        };
+        let json = match event {
+            Event::Started { workers } => common("started")
+                .field("mode", self.mode.as_str())
+                .field("seed", self.seed)
+                .field("workers", *workers),
+            Event::Found { finding, saved_to } => found(common(finding.kind.name()), finding)
+                .field("saved_to", saved_to.map(path)),
+            Event::NewCoverage {
+                input,
+                covered_edges,
+            } => common("new_coverage")
+                .field("input", hex(input))
+                .field("input_len", input.len())
+                .field("covered_edges", *covered_edges),
+            Event::Stopped { report } => common("stopped")
+                .field("execs", report.execs)
+                .field("execs_per_sec", report.execs_per_sec())
+                .field("total_crashes", report.total_crashes)
+                .field("unique_crashes", report.crashes.len())
+                .field("timeouts", report.timeouts)
+                .field("total_hangs", report.total_hangs)
+                .field("unique_hangs", report.hangs.len())
+                .field("total_out_of_memory", report.total_out_of_memory)
+                .field("unique_out_of_memory", report.out_of_memory.len())
+                .field(
+                    "covered_edges",
+                    report.coverage.as_ref().map(|coverage| coverage.covered_edges),
+                )
+                .field("interrupted", report.interrupted),
+        };
+        let written = writeln!(self.out, "{}", json.finish()).and_then(|()| self.out.flush());
+        if let Err(e) = written {
+            if !self.failed {
+                eprintln!("Could not write event to {:?}: {}", self.path, e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+/// The fields of a crash, hang or out-of-memory event.
+fn found(object: Object, finding: &Crash) -> Object {
+    object
+        .field("exit", finding.exit.to_string())
+        .field("signature", format!("{:016x}", finding.signature.stderr_hash))
+        .field(
+            "sanitizer",
+            finding.sanitizer.as_ref().map(|report| report.to_string()),
+        )
+        .field("input", hex(&finding.input))
+        .field("input_len", finding.input.len())
+        .field("duration", finding.duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_json_objects() {
+        let json = Object::new()
+            .field("text", "a \"quoted\"\\line\n\u{1}")
+            .field("count", 3u64)
+            .field("rate", 2.5f64)
+            .field("missing", None::<u64>)
+            .field("nested", Object::new().field("done", true))
+            .finish();
+        assert_eq!(
+            json,
+            r#"{"text":"a \"quoted\"\\line\n\u0001","count":3,"rate":2.500,"missing":null,"nested":{"done":true}}"#
+        );
+    }
+
+    #[test]
+    fn writes_the_stats_file() {
+        let path = std::env::temp_dir().join(format!("fuzzer-stats-{}.json", std::process::id()));
+        let mut stats_file = StatsFile::new(path.clone(), 7, 2);
+        stats_file.update(&Status {
+            elapsed: Duration::from_secs(2),
+            execs: 10,
+            total_crashes: 3,
+            unique_crashes: 1,
+            total_out_of_memory: 4,
+            unique_out_of_memory: 2,
+            ..Status::default()
+        });
+        let json = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(json.ends_with('\n'));
+        for field in [
+            r#""execs":10"#,
+            r#""execs_per_sec":5.000"#,
+            r#""total_crashes":3,"unique_crashes":1"#,
+            r#""total_hangs":0,"unique_hangs":0"#,
+            r#""total_out_of_memory":4,"unique_out_of_memory":2"#,
+            r#""seed":7,"jobs":2,"done":false"#,
+        ] {
+            assert!(json.contains(field), "{} not in {}", field, json);
+        }
+    }
+
+    #[test]
+    fn writes_inputs_as_hex() {
+        assert_eq!(hex(b""), "");
+        assert_eq!(hex(b"\x00a\xff"), "0061ff");
+    }
+}
@@ -5,12 +5,31 @@ pub mod runner;
 mod stoppable_loop;
 mod random_strings;
 mod flag;
+mod child_files;
+mod fork_server;
+mod harness;
 mod random_urls;
 mod delay;
+mod coverage;
+mod crash_dir;
+mod dedup;
 mod mutations;
+mod minimize;
+mod target;
+mod target_process;
+mod classify;
+mod sanitizer;
 
 // Re-export commonly used types and functions
-pub use fuzzer::Fuzzer;
+pub use fuzzer::{Fuzzer, Observation, Phase};
 pub use main_fuzzer::{MainFuzzer, FuzzingMode};
-pub use main_runner::MainRunner;
-pub use runner::{DefaultRunner, ProgramResult, Runner};
+pub use classify::{CrashPolicy, Failure};
+pub use crash_dir::{input_files, CrashDir};
+pub use dedup::{Crash, CrashSignature, FindingKind};
+pub use main_runner::{
+    Budget, CoverageSummary, Event, MainRunner, ParallelRunner, Report, Status, StopHandle,
+};
+pub use minimize::{minimize, Minimized};
+pub use runner::{DefaultRunner, ExitKind, ProgramResult, Runner};
+pub use sanitizer::{Access, AccessKind, Sanitizer, SanitizerReport};
+pub use target::{Delivered, InputDelivery, Target};
@@ -1,36 +1,291 @@
-use fuzzer::{FuzzingMode, MainFuzzer, MainRunner, Runner};
-use std::path::PathBuf;
+use cli::{Command, Options};
+use fuzzer::fuzzer::DefaultFuzzer;
+use fuzzer::{
+    input_files, CrashDir, Fuzzer, FuzzingMode, MainFuzzer, MainRunner, ParallelRunner, Runner,
+    StopHandle, Target,
+};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::{env, time::Duration};
+use json_output::{EventLog, StatsFile, STATS_INTERVAL};
+use status_screen::{StatusScreen, REFRESH_INTERVAL};
 
-fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
+mod cli;
+mod json_output;
+mod signals;
+mod status_screen;
 
-    if args.len() < 3 {
-        eprintln!("Usage: {} (--strings | --urls) <executable>", args[0]);
-        process::exit(1);
+fn read_corpus(dir: &Path) -> Result<Vec<Vec<u8>>, String> {
+    let files = input_files(dir).map_err(|e| format!("Could not read corpus {:?}: {}", dir, e))?;
+    files
+        .iter()
+        .map(|file| fs::read(file).map_err(|e| format!("Could not read {:?}: {}", file, e)))
+        .collect()
+}
+
+/// A runner with the options every command shares.
+fn runner<F: Fuzzer>(
+    target: Target,
+    fuzzer: F,
+    options: &Options,
+) -> Result<MainRunner<F>, String> {
+    let mut runner = if options.harness {
+        MainRunner::with_harness(target, fuzzer)
+            .map_err(|e| format!("Could not load harness {:?}: {}", options.executable, e))?
+    } else {
+        MainRunner::with_target(target, fuzzer)
+    };
+    if let Some(timeout) = options.timeout {
+        runner.set_timeout(timeout);
     }
+    runner.set_crash_policy(options.crash_policy.clone());
+    Ok(runner)
+}
 
-    let mode_arg = &args[1];
-    let executable = &args[2];
+/// A stop handle that Ctrl-C and SIGTERM stop (see the `signals` module).
+fn stop_on_signals() -> Result<StopHandle, String> {
+    let stop_handle = StopHandle::default();
+    signals::stop_on_signals(stop_handle.clone())
+        .map_err(|e| format!("Could not set up signal handling: {}", e))?;
+    Ok(stop_handle)
+}
 
-    let mode = FuzzingMode::from_arg(mode_arg)?;
+fn fuzz(mode: FuzzingMode, target: Target, options: &Options) -> Result<(), String> {
+    let stop_handle = stop_on_signals()?;
+    println!("Fuzzing mode: {:?}", mode);
+    if options.harness {
+        println!("Harness library: {:?}", target.executable);
+    } else {
+        println!("Target executable: {:?}", target.executable);
+    }
+    if !target.args.is_empty() {
+        println!("Target arguments: {:?}", target.args);
+    }
+    if let Some(name) = &options.input_env {
+        println!("Passing inputs in environment variable {}", name);
+    }
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+    if options.jobs > 1 {
+        println!("Running {} workers, seeded with {} + their number", options.jobs, seed);
+    }
 
-    let executable = PathBuf::from(executable);
-    if !executable.exists() {
-        eprintln!("Executable not found: {:?}", executable);
-        process::exit(1);
+    let crash_dir = match &options.output {
+        Some(dir) => {
+            let mut crash_dir = CrashDir::create(dir)
+                .map_err(|e| format!("Could not create output directory {:?}: {}", dir, e))?;
+            crash_dir.add_context("mode", format!("{:?}", mode));
+            crash_dir.add_context("target", target.executable.display());
+            if options.harness {
+                crash_dir.add_context("harness", true);
+            }
+            if !options.target_args.is_empty() {
+                crash_dir.add_context("args", options.target_args.join(" "));
+            }
+            if let Some(name) = &options.input_env {
+                crash_dir.add_context("input env", name);
+            }
+            if let Some(mib) = options.mem_limit {
+                crash_dir.add_context("mem limit", format!("{} MiB", mib));
+            }
+            if options.clear_env {
+                crash_dir.add_context("clear env", true);
+            }
+            for (key, value) in &options.env {
+                crash_dir.add_context("env", format!("{}={}", key, value));
+            }
+            Some(crash_dir)
+        }
+        None => None,
+    };
+
+    let corpus = match &options.corpus {
+        Some(dir) => {
+            let corpus = read_corpus(dir)?;
+            println!("Loaded {} corpus inputs from {:?}", corpus.len(), dir);
+            corpus
+        }
+        None => Vec::new(),
+    };
+
+    let mut runners = Vec::new();
+    for worker in 0..options.jobs {
+        // Every worker generates different inputs, and the first one the same
+        // inputs as a run without --jobs.
+        let seed = seed.wrapping_add(worker as u64);
+        let mut fuzzer = MainFuzzer::with_seed(mode, seed);
+        fuzzer.set_corpus(corpus.clone());
+        let mut runner = runner(target.clone(), fuzzer, options)?;
+        if options.hangs {
+            runner.report_hangs();
+        }
+        if options.fork_server {
+            runner.enable_fork_server();
+        }
+        if let Some(crash_dir) = &crash_dir {
+            let mut crash_dir = crash_dir.clone();
+            crash_dir.add_context("seed", seed);
+            runner.save_crashes_to(crash_dir);
+        }
+        if options.coverage {
+            runner
+                .enable_coverage()
+                .map_err(|e| format!("Could not set up coverage: {}", e))?;
+        }
+        runners.push(runner);
     }
 
-    println!("Fuzzing mode: {:?}", mode);
-    println!("Target executable: {:?}", executable);
+    let status_screen = StatusScreen::new();
+    let stats_file = options
+        .stats_file
+        .clone()
+        .map(|path| StatsFile::new(path, seed, options.jobs));
+    let event_log = match &options.events {
+        Some(path) => Some(
+            EventLog::create(path.clone(), format!("{:?}", mode), seed)
+                .map_err(|e| format!("Could not create event log {:?}: {}", path, e))?,
+        ),
+        None => None,
+    };
+    let report = if options.jobs == 1 {
+        let mut runner = runners.remove(0);
+        runner.set_stop_handle(stop_handle);
+        if let Some(mut screen) = status_screen {
+            runner.add_status_listener(REFRESH_INTERVAL, move |status| screen.update(status));
+        }
+        if let Some(mut stats_file) = stats_file {
+            runner.add_status_listener(STATS_INTERVAL, move |status| stats_file.update(status));
+        }
+        if let Some(mut event_log) = event_log {
+            runner.add_event_listener(move |event| event_log.log(event));
+        }
+        if options.continuous {
+            runner.run_continuous(options.budget)
+        } else {
+            runner.run_until_crash(options.budget)
+        }
+    } else {
+        let mut runner = ParallelRunner::new(runners);
+        runner.set_stop_handle(stop_handle);
+        if let Some(mut screen) = status_screen {
+            runner.add_status_listener(REFRESH_INTERVAL, move |status| screen.update(status));
+        }
+        if let Some(mut stats_file) = stats_file {
+            runner.add_status_listener(STATS_INTERVAL, move |status| stats_file.update(status));
+        }
+        if let Some(mut event_log) = event_log {
+            runner.add_event_listener(move |event| event_log.log(event));
+        }
+        if options.continuous {
+            runner.run_continuous(options.budget)
+        } else {
+            runner.run_until_crash(options.budget)
+        }
+    };
+    println!("{}", report);
+    report.error.map_or(Ok(()), Err)
+}
+
+fn minimize(target: Target, input: &Path, output: &Path, options: &Options) -> Result<(), String> {
+    let original = fs::read(input).map_err(|e| format!("Could not read {:?}: {}", input, e))?;
+    let stop_handle = stop_on_signals()?;
+    let mut runner = runner(target, DefaultFuzzer, options)?;
+    runner.set_stop_handle(stop_handle);
+    let minimized = fuzzer::minimize(&mut runner, &original)?;
+    fs::write(output, &minimized.input)
+        .map_err(|e| format!("Could not write {:?}: {}", output, e))?;
+    println!(
+        "Minimized {} bytes to {} bytes in {} executions: {:?}",
+        original.len(),
+        minimized.input.len(),
+        minimized.execs,
+        output
+    );
+    Ok(())
+}
+
+/// The input files to replay. Directories (like an --output directory) stand
+/// for every input file in them.
+fn replay_inputs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            inputs.push(path.clone());
+            continue;
+        }
+        let files = input_files(path).map_err(|e| format!("Could not read {:?}: {}", path, e))?;
+        inputs.extend(files);
+    }
+    Ok(inputs)
+}
 
-    let fuzzer = MainFuzzer::new(mode);
-    
+/// Replay every input, returning whether any of them crashed the executable.
+fn replay(target: Target, inputs: &[PathBuf], options: &Options) -> Result<bool, String> {
+    let stop_handle = stop_on_signals()?;
+    let mut runner = runner(target, DefaultFuzzer, options)?;
+    runner.set_stop_handle(stop_handle);
+    let mut any_crashed = false;
+    for path in replay_inputs(inputs)? {
+        let input = fs::read(&path).map_err(|e| format!("Could not read {:?}: {}", path, e))?;
+        let result = runner.run_with_input(&input)?;
+        any_crashed |= runner.is_crash(&result);
+        println!(
+            "{}: {} after {:.3}s",
+            path.display(),
+            result.exit,
+            result.duration.as_secs_f64()
+        );
+        if !result.stderr.is_empty() {
+            println!("stderr:\n{}", result.stderr.trim_end());
+        }
+    }
+    Ok(any_crashed)
+}
+
+fn main() -> Result<(), String> {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("{}", cli::USAGE);
+            process::exit(1);
+        }
+    };
 
-    let mut runner = MainRunner::new(executable, fuzzer);
-    runner.run();
-    // TODO: Report Metrics
+    // Bare names like `python3` are looked up in PATH when the target starts.
+    let is_path = options.executable.components().count() > 1;
+    if is_path && !options.executable.exists() {
+        eprintln!("Executable not found: {:?}", options.executable);
+        process::exit(1);
+    }
+    let mut target = Target::new(&options.executable).with_args(&options.target_args);
+    if let Some(name) = &options.input_env {
+        target = target.with_input_env(name);
+    }
+    if let Some(mib) = options.mem_limit {
+        target = target.with_mem_limit(mib.saturating_mul(1 << 20));
+    }
+    if options.clear_env {
+        target = target.with_clear_env();
+    }
+    for (key, value) in &options.env {
+        target.set_env(key, value);
+    }
+
+    match &options.command {
+        Command::Fuzz { mode } => fuzz(*mode, target, &options)?,
+        Command::Minimize { input, output } => minimize(target, input, output, &options)?,
+        Command::Replay {
+            inputs,
+            fail_on_crash,
+        } => {
+            let any_crashed = replay(target, inputs, &options)?;
+            if any_crashed && *fail_on_crash {
+                process::exit(1);
+            }
+        }
+    }
 
     Ok(())
 }
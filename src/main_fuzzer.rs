@@ -1,5 +1,5 @@
 use crate::random_strings;
-use crate::Fuzzer;
+use crate::fuzzer::{Fuzzer, Observation, Phase};
 use rand::seq::SliceRandom;
 use rand::Rng;
 use rand::{rngs::SmallRng, SeedableRng};
@@ -23,10 +23,15 @@ impl FuzzingMode {
     }
 }
 
+/// How likely the fuzzer is to mutate an input from the corpus instead of
+/// generating a new random input, when it has a corpus or interesting inputs.
+const CORPUS_MUTATION_PROBABILITY: f64 = 0.5;
+
 /// The current state of the fuzzer
 #[derive(Debug, Clone)]
 enum State {
-    /// This state sequentially yields the input from our big naughty strings list.
+    /// This state sequentially yields the inputs from the corpus, and then
+    /// the inputs from our big naughty strings list.
     PredefinedInput(usize),
     /// This state generates random input from the random input generators.
     Random,
@@ -45,63 +50,114 @@ pub struct MainFuzzer {
     state: State,
     mode: FuzzingMode,
     random_state: SmallRng,
+    /// Seed inputs given by the user.
+    corpus: Vec<Vec<u8>>,
+    /// Inputs that reached new coverage in the target.
+    interesting: Vec<Vec<u8>>,
 }
 
 impl MainFuzzer {
     pub fn new(mode: FuzzingMode) -> Self {
+        Self::with_rng(mode, SmallRng::from_entropy())
+    }
+
+    /// A fuzzer that generates the same inputs every time it is given the same
+    /// seed (and sees the same results from the target).
+    pub fn with_seed(mode: FuzzingMode, seed: u64) -> Self {
+        Self::with_rng(mode, SmallRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(mode: FuzzingMode, random_state: SmallRng) -> Self {
         Self {
             state: State::default(),
             mode,
-            random_state: SmallRng::from_entropy(),
+            random_state,
+            corpus: Vec::new(),
+            interesting: Vec::new(),
+        }
+    }
+
+    /// Give the fuzzer seed inputs. They are tried first, before the
+    /// predefined inputs, and are later mutated alongside the random inputs.
+    pub fn set_corpus(&mut self, corpus: Vec<Vec<u8>>) {
+        self.corpus = corpus;
+    }
+
+    fn next_predefined_input(&mut self, i: usize) -> Vec<u8> {
+        let corpus_len = self.corpus.len();
+        let (output, reached_end) = if i < corpus_len {
+            let reached_end = i + 1 >= corpus_len + predefined_inputs::get(|input| input.len());
+            (self.corpus[i].clone(), reached_end)
+        } else {
+            let i = i - corpus_len;
+            predefined_inputs::get(|input| (input[i].to_vec(), i + 1 >= input.len()))
+        };
+        self.state = if reached_end {
+            State::Random
+        } else {
+            State::PredefinedInput(i + 1)
+        };
+        output
+    }
+
+    /// Maybe pick an input from the corpus or the interesting inputs to start
+    /// mutating.
+    fn pick_corpus_input(&mut self) -> Option<Vec<u8>> {
+        let len = self.corpus.len() + self.interesting.len();
+        if len == 0 || !self.random_state.gen_bool(CORPUS_MUTATION_PROBABILITY) {
+            return None;
+        }
+        let i = self.random_state.gen_range(0..len);
+        let input = match self.corpus.get(i) {
+            Some(input) => input,
+            None => &self.interesting[i - self.corpus.len()],
+        };
+        Some(input.clone())
+    }
+
+    fn mutate_previous_input(&mut self) -> Vec<u8> {
+        let State::Mutate { ref mut previous_input } = self.state else {
+            unreachable!("only called in the mutate state");
+        };
+        mutate(previous_input, &mut self.random_state);
+        if self.random_state.gen_bool(0.5) {
+            previous_input.clone()
+        } else {
+            let previous_input = previous_input.clone();
+            self.state = State::Random;
+            previous_input
         }
     }
 
     fn generate_string_input(&mut self) -> Vec<u8> {
         match self.state {
-            State::PredefinedInput(i) => {
-                let (output, reached_end) =
-                    predefined_inputs::get(|input| (input[i], i + 1 >= input.len()));
-                self.state = if reached_end {
-                    State::Random
-                } else {
-                    State::PredefinedInput(i + 1)
-                };
-                output.to_vec()
+            State::PredefinedInput(i) => self.next_predefined_input(i),
+            State::Random => {
+                if let Some(previous_input) = self.pick_corpus_input() {
+                    self.state = State::Mutate { previous_input };
+                    return self.mutate_previous_input();
+                }
+                generate_random_input(&mut self.random_state)
             }
-            State::Random => generate_random_input(&mut self.random_state),
-            State::Mutate { .. } => unsafe { std::hint::unreachable_unchecked() },
+            State::Mutate { .. } => self.mutate_previous_input(),
         }
     }
     
     fn generate_url_input(&mut self) -> Vec<u8> {
         match self.state {
-            State::PredefinedInput(i) => {
-                let (output, reached_end) =
-                    predefined_inputs::get(|input| (input[i], i + 1 >= input.len()));
-                self.state = if reached_end {
-                    State::Random 
-                } else {
-                    State::PredefinedInput(i + 1)
-                };
-                output.to_vec()
-            }
+            State::PredefinedInput(i) => self.next_predefined_input(i),
             State::Random => {
+                if let Some(previous_input) = self.pick_corpus_input() {
+                    self.state = State::Mutate { previous_input };
+                    return self.mutate_previous_input();
+                }
                 let ret = random_urls::generate_random_url_input(&mut self.random_state);
                 if self.random_state.gen_bool(0.5) {
                     self.state = State::Mutate { previous_input: ret.clone() };
                 } 
                 ret
             }
-            State::Mutate { ref mut previous_input } => {
-                mutate(previous_input, &mut self.random_state);
-                if self.random_state.gen_bool(0.5) {
-                    previous_input.clone()
-                } else {
-                    let previous_input = previous_input.clone();
-                    self.state = State::Random;
-                    previous_input
-                }
-            }
+            State::Mutate { .. } => self.mutate_previous_input(),
         }
     }
 }
@@ -173,7 +229,12 @@ const MUTATIONS: &[(Weight, Mutation)] = &[
     (5, add_important_substring),
 ];
 
-fn mutate(input: &mut Vec<u8>, random_state: &mut SmallRng) {
+pub(crate) fn mutate(input: &mut Vec<u8>, random_state: &mut SmallRng) {
+    // The mutations all pick a position in the input, so give them one.
+    if input.is_empty() {
+        input.push(random_state.gen());
+        return;
+    }
     let (_, mutation) = MUTATIONS.choose_weighted(&mut *random_state, |(w, _)| *w).unwrap();
     mutation(input, random_state);
 }
@@ -185,6 +246,25 @@ impl Fuzzer for MainFuzzer {
             FuzzingMode::Urls => {self.generate_url_input()}
         }
     }
+
+    fn observe(&mut self, observation: &Observation) {
+        // Crashing inputs are already saved, mutating them mostly finds the
+        // same crash again.
+        if observation.new_coverage == Some(true) && !observation.exit.is_crash() {
+            self.interesting.push(observation.input.to_vec());
+        }
+    }
+
+    fn phase(&self) -> Option<Phase> {
+        Some(match self.state {
+            State::PredefinedInput(index) => Phase::Predefined {
+                index,
+                total: self.corpus.len() + predefined_inputs::get(|input| input.len()),
+            },
+            State::Random => Phase::Random,
+            State::Mutate { .. } => Phase::Mutate,
+        })
+    }
 }
 
 const SHORT_STRING_GENERATOR: random_strings::ByteGenerator = random_strings::string::<1, 10>();
@@ -236,6 +316,8 @@ fn generate_random_input(rng: &mut SmallRng) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::ExitKind;
+    use std::time::Duration;
 
     #[test]
     fn fuzzer_moves_to_next_state() {
@@ -244,7 +326,92 @@ mod tests {
         for _ in 0..n {
             fuzz.generate_input();
         }
-        assert!(std::matches!(fuzz.state, State::Random { .. }));
+        assert!(std::matches!(fuzz.state, State::Random));
+    }
+
+    #[test]
+    fn fuzzer_yields_corpus_first() {
+        let mut fuzz = MainFuzzer::new(FuzzingMode::Strings);
+        fuzz.set_corpus(vec![b"seed one".to_vec(), b"seed two".to_vec()]);
+        assert_eq!(fuzz.generate_input(), b"seed one");
+        assert_eq!(fuzz.generate_input(), b"seed two");
+        let first_predefined = predefined_inputs::get(|input| input[0].to_vec());
+        assert_eq!(fuzz.generate_input(), first_predefined);
+
+        let n = predefined_inputs::get(|input| input.len());
+        for _ in 1..n {
+            fuzz.generate_input();
+        }
+        assert!(std::matches!(fuzz.state, State::Random));
+    }
+
+    #[test]
+    fn fuzzer_mutates_corpus_inputs() {
+        let mut fuzz = MainFuzzer::new(FuzzingMode::Strings);
+        fuzz.set_corpus(vec![Vec::new()]);
+        fuzz.state = State::Random;
+        let mutated_corpus = (0..100).any(|_| {
+            fuzz.generate_input();
+            std::matches!(&fuzz.state, State::Mutate { previous_input } if previous_input.len() < 3)
+        });
+        assert!(mutated_corpus);
+    }
+
+    #[test]
+    fn fuzzer_is_reproducible_with_seed() {
+        for mode in [FuzzingMode::Strings, FuzzingMode::Urls] {
+            let mut first = MainFuzzer::with_seed(mode, 1234);
+            let mut second = MainFuzzer::with_seed(mode, 1234);
+            first.set_corpus(vec![b"seed".to_vec()]);
+            second.set_corpus(vec![b"seed".to_vec()]);
+            first.state = State::Random;
+            second.state = State::Random;
+            for _ in 0..1000 {
+                assert_eq!(first.generate_input(), second.generate_input());
+            }
+        }
+    }
+
+    #[test]
+    fn fuzzer_tells_its_phase() {
+        let mut fuzz = MainFuzzer::with_seed(FuzzingMode::Strings, 1);
+        fuzz.set_corpus(vec![b"seed".to_vec()]);
+        let total = 1 + predefined_inputs::get(|input| input.len());
+        assert_eq!(fuzz.phase(), Some(Phase::Predefined { index: 0, total }));
+        fuzz.generate_input();
+        assert_eq!(fuzz.phase(), Some(Phase::Predefined { index: 1, total }));
+        for _ in 1..total {
+            fuzz.generate_input();
+        }
+        assert_eq!(fuzz.phase(), Some(Phase::Random));
+        fuzz.state = State::Mutate { previous_input: b"seed".to_vec() };
+        assert_eq!(fuzz.phase(), Some(Phase::Mutate));
+    }
+
+    #[test]
+    fn fuzzer_keeps_inputs_with_new_coverage() {
+        let mut fuzz = MainFuzzer::new(FuzzingMode::Strings);
+        let mut observation = Observation {
+            input: b"new edge",
+            exit: ExitKind::Code(0),
+            duration: Duration::from_millis(1),
+            new_coverage: Some(true),
+            new_crash: false,
+        };
+        fuzz.observe(&observation);
+        observation.exit = ExitKind::Signal(11);
+        fuzz.observe(&observation);
+        observation.exit = ExitKind::Code(0);
+        observation.new_coverage = Some(false);
+        fuzz.observe(&observation);
+        assert_eq!(fuzz.interesting, vec![b"new edge".to_vec()]);
+
+        fuzz.state = State::Random;
+        let mutated_interesting_input = (0..100).any(|_| {
+            fuzz.generate_input();
+            std::matches!(&fuzz.state, State::Mutate { previous_input } if previous_input.starts_with(b"new"))
+        });
+        assert!(mutated_interesting_input);
     }
 
     #[test]
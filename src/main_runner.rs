@@ -1,28 +1,74 @@
-use crate::fuzzer::Fuzzer;
-use crate::runner::{ProgramResult, Runner};
-use shared_child::SharedChild;
+use crate::classify::{self, CrashPolicy};
+use crate::coverage::{self, Coverage};
+use crate::crash_dir::CrashDir;
+use crate::dedup::{Bucketed, Crash, CrashBuckets, FindingKind};
+use crate::fork_server::{ForkServer, ForkedChild};
+use crate::fuzzer::{Fuzzer, Observation, Phase};
+use crate::harness::Supervisor;
+use crate::runner::{ExitKind, ProgramResult, Runner};
+use crate::stoppable_loop::{LoopAction, StoppableLoop};
+use crate::target::{Delivered, InputDelivery, InputFile, Target};
+use crate::target_process::{Running, TargetProcess};
 use std::{
     fmt::{self, Display, Formatter},
-    io::{self, Write},
-    path::{Path, PathBuf},
-    sync::Arc,
-    time::Duration,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
-use crate::flag::Flag;
-use crate::delay::Delayer;
+use crate::flag::{Flag, WaitableFlag};
+use crate::delay::{self, Delayer};
+mod event;
+mod parallel;
+mod report;
+mod status;
+
+pub use event::Event;
+use event::EventListener;
+pub use parallel::ParallelRunner;
+pub use report::{Budget, CoverageSummary, Report};
+pub use status::Status;
+use status::StatusListener;
 
 
 const SINGLE_EXECUTION_TIMEOUT_SECS: f32 = 1.05;
+/// Inputs that time out are only reported as hangs if they also time out with
+/// a timeout this many times longer.
+const HANG_CONFIRMATION_FACTOR: u32 = 4;
+/// How much of the target's stdout and stderr is kept. Sanitizer reports fit
+/// easily, while a target stuck printing in a loop cannot use up our memory.
+const MAX_CAPTURED_OUTPUT: u64 = 1 << 20;
 
 /// This runner takes a fuzzer, a total timeout, and an executable path. It
 /// runs the executable with the input generated by the fuzzer until the total
 /// timeout is reached.
 ///
-/// Every execution has it's own individual timeout of `SINGLE_EXECUTION_TIMEOUT_SECS`.
+/// Every execution has it's own individual timeout, by default
+/// `SINGLE_EXECUTION_TIMEOUT_SECS`.
 pub struct MainRunner<F: Fuzzer> {
-    executable: PathBuf,
+    target: Target,
     fuzzer: F,
     single_execution_timeout: Duration,
+    /// Whether inputs that time out are findings.
+    report_hangs: bool,
+    crash_policy: CrashPolicy,
+    delayer: Delayer<Box<dyn FnOnce() + Send + 'static>>,
+    crash_dir: Option<CrashDir>,
+    coverage: Option<Coverage>,
+    /// Whether to run the target through a fork server, while it works.
+    use_fork_server: bool,
+    /// Started on the first execution, so it gets the target's final
+    /// environment.
+    fork_server: Option<ForkServer>,
+    /// Runs the inputs in-process instead, for harness libraries.
+    harness: Option<Supervisor>,
+    /// The `@@` file of spawned targets, rewritten for every input.
+    input_file: Option<InputFile>,
+    status_listeners: Vec<StatusListener>,
+    event_listeners: Vec<EventListener>,
+    stop_handle: StopHandle,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -32,66 +78,347 @@ enum WaitWithTimeoutResult {
 }
 impl<T: Fuzzer> MainRunner<T> {
     pub fn new(executable: PathBuf, fuzzer: T) -> Self {
+        Self::with_target(Target::new(executable), fuzzer)
+    }
+
+    /// A runner for a target with arguments, which may ask for the input in
+    /// a file or an argument instead of stdin (see the `target` module).
+    pub fn with_target(target: Target, fuzzer: T) -> Self {
         Self {
-            executable,
+            target,
             fuzzer,
             single_execution_timeout: Duration::from_secs_f32(SINGLE_EXECUTION_TIMEOUT_SECS),
+            report_hangs: false,
+            crash_policy: CrashPolicy::default(),
+            delayer: Delayer::new(),
+            crash_dir: None,
+            coverage: None,
+            use_fork_server: false,
+            fork_server: None,
+            harness: None,
+            input_file: None,
+            status_listeners: Vec::new(),
+            event_listeners: Vec::new(),
+            stop_handle: StopHandle::default(),
+        }
+    }
+
+    /// A runner for a libFuzzer harness: the target's executable is a shared
+    /// library exporting `LLVMFuzzerTestOneInput`, which is called with every
+    /// input in a supervised child (see the `harness` module). Only the
+    /// target's memory limit applies; the harness gets no arguments or
+    /// environment of its own.
+    pub fn with_harness(target: Target, fuzzer: T) -> io::Result<Self> {
+        let harness = Supervisor::new(&target)?;
+        Ok(Self {
+            harness: Some(harness),
+            ..Self::with_target(target, fuzzer)
+        })
+    }
+
+    /// Let this handle stop the runner's fuzzing runs, and kill the inputs it
+    /// runs one by one (`Runner::run_with_input`), from another thread.
+    pub fn set_stop_handle(&mut self, stop_handle: StopHandle) {
+        self.stop_handle = stop_handle;
+    }
+
+    /// Call `listener` with the status of every fuzzing run every `interval`,
+    /// and once more when the run is over.
+    pub fn add_status_listener(
+        &mut self,
+        interval: Duration,
+        listener: impl FnMut(&Status) + Send + 'static,
+    ) {
+        self.status_listeners.push(StatusListener {
+            interval,
+            listener: Box::new(listener),
+        });
+    }
+
+    /// Call `listener` with everything that happens during fuzzing runs (see
+    /// `Event`), as it happens.
+    pub fn add_event_listener(&mut self, listener: impl FnMut(&Event) + Send + 'static) {
+        self.event_listeners.push(Box::new(listener));
+    }
+
+    /// How long a single execution may run before it is killed.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.single_execution_timeout = timeout;
+    }
+
+    /// Treat inputs that time out as findings (hangs), like infinite loops or
+    /// catastrophic regex backtracking. A timeout is confirmed by running the
+    /// input again with a longer timeout, and hangs are kept and saved
+    /// separately from crashes. Unlike crashes, they never stop the run.
+    pub fn report_hangs(&mut self) {
+        self.report_hangs = true;
+    }
+
+    /// Which failures of the target count as crashes, for fuzzing as well as
+    /// for `Runner::is_crash`. By default every failure does.
+    pub fn set_crash_policy(&mut self, crash_policy: CrashPolicy) {
+        self.crash_policy = crash_policy;
+    }
+
+    /// Save every new crash found to this directory as soon as it is found.
+    pub fn save_crashes_to(&mut self, crash_dir: CrashDir) {
+        self.crash_dir = Some(crash_dir);
+    }
+
+    /// Collect edge coverage from the target (see the `coverage` module), and
+    /// tell the fuzzer which of its inputs reach new edges.
+    pub fn enable_coverage(&mut self) -> io::Result<()> {
+        if self.harness.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "coverage is not collected from harnesses",
+            ));
+        }
+        let coverage = Coverage::new()?;
+        self.target
+            .set_env(coverage::SHM_ENV_VAR, coverage.shm_id().to_string());
+        self.coverage = Some(coverage);
+        Ok(())
+    }
+
+    /// Run the target through a fork server when fuzzing (see the
+    /// `fork_server` module), for targets linked with its runtime. Targets
+    /// that do not start one are run with a plain exec for every input.
+    pub fn enable_fork_server(&mut self) {
+        self.use_fork_server = true;
+    }
+
+    /// Have the fork server run this input, starting the server first if
+    /// needed. Returns `None` if there is no working fork server.
+    fn fork(&mut self, input: &[u8]) -> Option<ForkedChild> {
+        if !self.use_fork_server {
+            return None;
+        }
+        if self.fork_server.is_none() {
+            match ForkServer::start(&self.target, self.single_execution_timeout) {
+                Ok(Some(fork_server)) => self.fork_server = Some(fork_server),
+                Ok(None) => {
+                    eprintln!(
+                        "The target did not start a fork server, running it with a plain exec \
+                         instead. Was it linked with resources/forkserver/forkserver_runtime.c?"
+                    );
+                    self.use_fork_server = false;
+                    return None;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Could not start a fork server ({}), running the target with a plain \
+                         exec instead.",
+                        e
+                    );
+                    self.use_fork_server = false;
+                    return None;
+                }
+            }
+        }
+        let fork_server = self.fork_server.as_mut()?;
+        match fork_server.run(input) {
+            Ok(child) => Some(child),
+            Err(e) => {
+                self.drop_fork_server(e);
+                None
+            }
         }
     }
+
+    /// Give up on a fork server that failed, and exec the target for every
+    /// input from now on.
+    fn drop_fork_server(&mut self, error: io::Error) {
+        eprintln!(
+            "The fork server failed ({}), running the target with a plain exec instead.",
+            error
+        );
+        self.fork_server = None;
+        self.use_fork_server = false;
+    }
+
+    /// What the last input run by the harness or the fork server wrote to
+    /// stderr. Spawned targets have theirs read from a pipe instead.
+    fn server_stderr(&self) -> io::Result<Vec<u8>> {
+        match (&self.harness, &self.fork_server) {
+            (Some(harness), _) => harness.stderr(MAX_CAPTURED_OUTPUT),
+            (None, Some(fork_server)) => fork_server.stderr(MAX_CAPTURED_OUTPUT),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
+    /// Run a single input outside of fuzzing, in the harness if there is
+    /// one. The harness' stdout is not captured.
+    fn execute(&mut self, input: &[u8], timeout: Duration) -> io::Result<ProgramResult> {
+        let Some(harness) = &mut self.harness else {
+            return execute(
+                &self.target,
+                input,
+                &mut self.input_file,
+                timeout,
+                &self.delayer,
+                &self.stop_handle,
+            );
+        };
+        let start_time = Instant::now();
+        let run: Arc<dyn Running> = Arc::new(harness.run(input)?);
+        let result = self
+            .stop_handle
+            .watch(&run, || wait_with_timeout(run.clone(), timeout, &self.delayer));
+        let exit = match result? {
+            WaitWithTimeoutResult::Finished(exit_status) => ExitKind::from(exit_status),
+            WaitWithTimeoutResult::Timeout => ExitKind::Timeout,
+        };
+        let duration = start_time.elapsed();
+        Ok(ProgramResult {
+            stdout: Vec::new(),
+            stderr: String::from_utf8_lossy(&self.server_stderr()?).into_owned(),
+            exit,
+            duration,
+        })
+    }
 }
 
-/// Spawn a process with the given executable path and write the input to its
-/// stdin.
-fn spawn_with_stdin(executable: &Path, input: &[u8]) -> io::Result<SharedChild> {
-    // TODO: Can we limit Command::new to only absolute paths?
-    let child = SharedChild::spawn(
-        std::process::Command::new(executable)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null()),
-    )?;
-    child
-        .take_stdin()
-        .as_mut()
-        .ok_or(io::Error::new(
-            io::ErrorKind::Other,
-            "could not open stdin pipe",
-        ))?
-        .write_all(input)?;
+/// Which of the child's outputs are piped back to us. Outputs that are not
+/// captured are discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    Stderr,
+    All,
+}
+
+/// Spawn the target and hand it the input the way it asks for it. The captured
+/// outputs can be taken from the returned child. The input file, if the target
+/// needs one, is created in `input_file` the first time, and must be kept until
+/// the child exits.
+fn spawn(
+    target: &Target,
+    input: &[u8],
+    input_file: &mut Option<InputFile>,
+    capture: Capture,
+) -> io::Result<TargetProcess> {
+    let stdout = match capture {
+        Capture::All => Stdio::piped(),
+        Capture::Stderr => Stdio::null(),
+    };
+    let use_stdin = target.input_delivery() == InputDelivery::Stdin;
+    let stdin = if use_stdin { Stdio::piped() } else { Stdio::null() };
+    let mut command = target.command(input, input_file)?;
+    let child = TargetProcess::spawn(command.stdin(stdin).stdout(stdout).stderr(Stdio::piped()))?;
+    if use_stdin {
+        let stdin = child
+            .take_stdin()
+            .ok_or(io::Error::other("could not open stdin pipe"))?;
+        write_in_background(stdin, input.to_vec());
+    }
     Ok(child)
 }
 
+/// Write the input to the child's stdin on another thread, and close it. A
+/// child that does not read an input larger than the pipe would block us
+/// before it can time out, or be stopped.
+fn write_in_background(mut stdin: impl Write + Send + 'static, input: Vec<u8>) {
+    thread::spawn(move || {
+        // The child may exit (or crash!) before reading all of its input, or
+        // be killed because it does not read it.
+        let _ = stdin.write_all(&input);
+    });
+}
+
+/// Whether starting a child failed for lack of resources that may be freed
+/// soon, rather than because it can never start.
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::EAGAIN | libc::EMFILE | libc::ENFILE)
+    )
+}
+
+/// A thread reading one of the outputs of a child.
+type Reader = thread::JoinHandle<io::Result<Vec<u8>>>;
+
+/// Read everything from a pipe on another thread, so a child that fills one
+/// pipe never blocks while we are waiting on it. Only the first
+/// `MAX_CAPTURED_OUTPUT` bytes are kept, the rest is read and dropped.
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> Reader {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.by_ref().take(MAX_CAPTURED_OUTPUT).read_to_end(&mut buffer)?;
+            io::copy(&mut pipe, &mut io::sink())?;
+        }
+        Ok(buffer)
+    })
+}
+
+fn join_reader(handle: Reader) -> io::Result<Vec<u8>> {
+    handle.join().expect("pipe reader thread panicked")
+}
+
+/// Run the target once with the given input, wait for it to finish (or time
+/// out) and collect everything it wrote to stdout and stderr. The input file
+/// is reused like in `spawn`, and the target is killed if the stop handle
+/// stops.
+pub(crate) fn execute(
+    target: &Target,
+    input: &[u8],
+    input_file: &mut Option<InputFile>,
+    timeout: Duration,
+    delayer: &Delayer<Box<dyn FnOnce() + Send + 'static>>,
+    stop_handle: &StopHandle,
+) -> io::Result<ProgramResult> {
+    let start_time = Instant::now();
+    let child = spawn(target, input, input_file, Capture::All)?;
+    let stdout = read_in_background(child.take_stdout());
+    let stderr = read_in_background(child.take_stderr());
+    let child: Arc<dyn Running> = Arc::new(child);
+    let result = stop_handle.watch(&child, || wait_with_timeout(child.clone(), timeout, delayer));
+    let exit = match result? {
+        WaitWithTimeoutResult::Finished(exit_status) => ExitKind::from(exit_status),
+        WaitWithTimeoutResult::Timeout => ExitKind::Timeout,
+    };
+    let duration = start_time.elapsed();
+    Ok(ProgramResult {
+        stdout: join_reader(stdout)?,
+        stderr: String::from_utf8_lossy(&join_reader(stderr)?).into_owned(),
+        exit,
+        duration,
+    })
+}
+
+/// Wait for the child, killing it after the timeout. Fails if we cannot tell
+/// how it exited, like when the fork server that ran it died.
 fn wait_with_timeout(
-    child: Arc<SharedChild>,
+    child: Arc<dyn Running>,
     timeout: Duration,
     delayer: &Delayer<Box<dyn FnOnce() + Send + 'static>>,
-) -> WaitWithTimeoutResult {
+) -> io::Result<WaitWithTimeoutResult> {
     let was_killed = Flag::default();
 
-    // Kill the child process after the timeout
+    // Kill the child process, and everything it started, after the timeout
     {
         let raise_was_killed = was_killed.get_raise();
         let child = child.clone();
         delayer.set(timeout, Box::new(move || {
             raise_was_killed();
-            child.kill().expect("could not kill child process");
+            child.kill();
         }));
     }
 
-    let exit_status = child.wait().expect("could not wait for child process");
+    let exit_status = child.wait();
     delayer.cancel();
     if was_killed.is_raised() {
-        WaitWithTimeoutResult::Timeout
+        Ok(WaitWithTimeoutResult::Timeout)
     } else {
-        WaitWithTimeoutResult::Finished(exit_status)
+        exit_status.map(WaitWithTimeoutResult::Finished)
     }
 }
 
-struct InputFoundPrinter(Vec<u8>);
+pub(crate) struct InputFoundPrinter<'a>(pub &'a [u8]);
 
-impl Display for InputFoundPrinter {
+impl Display for InputFoundPrinter<'_> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let bytes = &self.0;
+        let bytes = self.0;
         if let Ok(string) = std::str::from_utf8(bytes) {
             write!(f, "{}", string)
         } else {
@@ -100,37 +427,660 @@ impl Display for InputFoundPrinter {
     }
 }
 
-impl<F: Fuzzer> MainRunner<F> {
-    fn search_for_input(&mut self) -> Vec<u8> {
-        let delayer = Delayer::new();
-        loop {
-            let input = self.fuzzer.generate_input();
-            let child = spawn_with_stdin(&self.executable, &input)
-                .expect("could not spawn child process");
-            let result = wait_with_timeout(child.into(), self.single_execution_timeout, &delayer);
-            match result {
-                WaitWithTimeoutResult::Finished(exit_status) => {
-
-                    if !exit_status.success() {
-                        return input;
+/// A single execution of the target that is currently running.
+struct Execution {
+    /// `None` for an input that was skipped, with nothing running.
+    child: Option<Arc<dyn Running>>,
+    was_stopped: Flag,
+}
+
+/// An execution that was started, waiting to be finished.
+struct Pending {
+    execution: Arc<Execution>,
+    child: Arc<dyn Running>,
+    input: Vec<u8>,
+    /// Reads the stderr of a spawned target. A forked one writes it to the
+    /// fork server's file, and a harness to the supervisor's.
+    stderr: Option<Reader>,
+    start_time: Instant,
+}
+
+/// Stops fuzzing runs from the outside, like on Ctrl-C. Once stopped it stays
+/// stopped, so a run started later on stops right away.
+#[derive(Clone, Default)]
+pub struct StopHandle(Arc<Mutex<StopHandleState>>);
+
+#[derive(Default)]
+struct StopHandleState {
+    stopped: bool,
+    running: Option<Arc<Campaign>>,
+    /// Single executions outside of the fuzzing loop, like replayed inputs.
+    executions: Vec<Arc<dyn Running>>,
+}
+
+impl StopHandle {
+    /// Stop the current run, if any, and every later one. The interrupted
+    /// run still returns a report of everything up to now.
+    pub fn stop(&self) {
+        let running = {
+            let mut state = self.0.lock().unwrap();
+            state.stopped = true;
+            state.running.take()
+        };
+        if let Some(campaign) = running {
+            campaign.interrupt();
+        }
+        self.kill_executions();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.lock().unwrap().stopped
+    }
+
+    /// Kill the single executions that are still running, like before
+    /// exiting without waiting for them. Targets run in process groups of
+    /// their own, so they do not get the terminal's Ctrl-C.
+    pub fn kill_executions(&self) {
+        for execution in &self.0.lock().unwrap().executions {
+            execution.kill();
+        }
+    }
+
+    /// Wait for a single execution, so that it is killed if this handle stops.
+    fn watch<R>(&self, execution: &Arc<dyn Running>, wait: impl FnOnce() -> R) -> R {
+        {
+            let mut state = self.0.lock().unwrap();
+            if state.stopped {
+                execution.kill();
+            }
+            state.executions.push(execution.clone());
+        }
+        let result = wait();
+        let mut state = self.0.lock().unwrap();
+        state.executions.retain(|running| !Arc::ptr_eq(running, execution));
+        result
+    }
+
+    /// Run a campaign, so it can be stopped by this handle.
+    fn run(&self, campaign: &Arc<Campaign>, run: impl FnOnce()) {
+        {
+            let mut state = self.0.lock().unwrap();
+            if state.stopped {
+                drop(state);
+                campaign.interrupt();
+            } else {
+                state.running = Some(campaign.clone());
+            }
+        }
+        run();
+        self.0.lock().unwrap().running = None;
+    }
+}
+
+/// What the workers of one fuzzing run share: the crashes found, the
+/// statistics, and a way to stop all of them.
+struct Campaign {
+    start_time: Instant,
+    budget: Budget,
+    stop_on_first_crash: bool,
+    buckets: Mutex<CrashBuckets>,
+    hangs: Mutex<CrashBuckets>,
+    out_of_memory: Mutex<CrashBuckets>,
+    report: Mutex<Report>,
+    stops: Mutex<Stops>,
+    /// Raised when the run was stopped from the outside.
+    interrupted: Flag,
+    /// What the fuzzer of the first worker is doing.
+    phase: Mutex<Option<Phase>>,
+    /// When the last new crash, hang or out-of-memory failure was found.
+    last_finding: Mutex<Option<Instant>>,
+    event_listeners: Mutex<Vec<EventListener>>,
+}
+
+#[derive(Default)]
+struct Stops {
+    stopped: bool,
+    /// Stops the loop of every worker that is running.
+    workers: Vec<Box<dyn FnOnce() + Send + 'static>>,
+}
+
+impl Campaign {
+    fn new(
+        budget: Budget,
+        stop_on_first_crash: bool,
+        event_listeners: Vec<EventListener>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            start_time: Instant::now(),
+            budget,
+            stop_on_first_crash,
+            buckets: Mutex::default(),
+            hangs: Mutex::new(CrashBuckets::new(FindingKind::Hang)),
+            out_of_memory: Mutex::new(CrashBuckets::new(FindingKind::OutOfMemory)),
+            report: Mutex::default(),
+            stops: Mutex::default(),
+            interrupted: Flag::default(),
+            phase: Mutex::default(),
+            last_finding: Mutex::default(),
+            event_listeners: Mutex::new(event_listeners),
+        })
+    }
+
+    fn emit(&self, event: &Event) {
+        for listener in self.event_listeners.lock().unwrap().iter_mut() {
+            listener(event);
+        }
+    }
+
+    /// The event listeners, back from a run that is over.
+    fn take_event_listeners(&self) -> Vec<EventListener> {
+        std::mem::take(&mut *self.event_listeners.lock().unwrap())
+    }
+
+    /// Add a finding to its buckets and keep the crash directory up to date
+    /// with it, then tell about it if it is a new one. The listeners are only
+    /// called once the buckets are unlocked, so other workers do not wait on
+    /// them. Returns whether the finding is a new one.
+    fn keep(
+        &self,
+        buckets: &Mutex<CrashBuckets>,
+        crash_dir: Option<&CrashDir>,
+        input: Vec<u8>,
+        exit: ExitKind,
+        stderr: String,
+        duration: Duration,
+    ) -> bool {
+        let (new_finding, saved_to) = {
+            let mut buckets = buckets.lock().unwrap();
+            let (bucketed, crash) = buckets.add(input, exit, stderr, duration);
+            let saved_to = crash_dir.and_then(|crash_dir| save_crash(crash_dir, &bucketed, crash));
+            ((bucketed == Bucketed::New).then(|| crash.clone()), saved_to)
+        };
+        let Some(crash) = new_finding else {
+            return false;
+        };
+        self.found_new();
+        self.emit(&Event::Found {
+            finding: &crash,
+            saved_to: saved_to.as_deref(),
+        });
+        true
+    }
+
+    fn status(&self, done: bool) -> Status {
+        let (execs, timeouts) = {
+            let report = self.report.lock().unwrap();
+            (report.execs, report.timeouts)
+        };
+        let (total_crashes, unique_crashes) = {
+            let buckets = self.buckets.lock().unwrap();
+            (buckets.total_hits(), buckets.buckets())
+        };
+        let (total_hangs, unique_hangs) = {
+            let hangs = self.hangs.lock().unwrap();
+            (hangs.total_hits(), hangs.buckets())
+        };
+        let (total_out_of_memory, unique_out_of_memory) = {
+            let out_of_memory = self.out_of_memory.lock().unwrap();
+            (out_of_memory.total_hits(), out_of_memory.buckets())
+        };
+        Status {
+            elapsed: self.start_time.elapsed(),
+            execs,
+            total_crashes,
+            unique_crashes,
+            timeouts,
+            total_hangs,
+            unique_hangs,
+            total_out_of_memory,
+            unique_out_of_memory,
+            phase: *self.phase.lock().unwrap(),
+            since_last_finding: self.last_finding.lock().unwrap().map(|found| found.elapsed()),
+            done,
+        }
+    }
+
+    /// Run the campaign, calling the listeners with its status on the way.
+    fn run_watched(&self, listeners: &mut [StatusListener], run: impl FnOnce()) {
+        let done = WaitableFlag::default();
+        thread::scope(|scope| {
+            for StatusListener { interval, listener } in listeners {
+                let wait = done.wait();
+                scope.spawn(move || loop {
+                    let timed_out = wait.until_raised_timeout(*interval).timed_out();
+                    listener(&self.status(!timed_out));
+                    if !timed_out {
+                        break;
                     }
+                });
+            }
+            run();
+            done.get_raise()();
+        });
+    }
+
+    /// Remember that something new was found just now.
+    fn found_new(&self) {
+        *self.last_finding.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Stop every worker once the time budget runs out. Returns a closure
+    /// that cancels this.
+    fn stop_after_time_limit(self: &Arc<Self>) -> Option<impl FnOnce()> {
+        let campaign = self.clone();
+        self.budget
+            .max_time
+            .map(|max_time| delay::cancelable_delay(max_time, move || campaign.stop()))
+    }
+
+    /// Stop every worker, and any worker added later on.
+    fn stop(&self) {
+        let workers = {
+            let mut stops = self.stops.lock().unwrap();
+            stops.stopped = true;
+            std::mem::take(&mut stops.workers)
+        };
+        for stop_worker in workers {
+            stop_worker();
+        }
+    }
+
+    fn interrupt(&self) {
+        self.interrupted.raise();
+        self.stop();
+    }
+
+    /// Run a worker until the campaign is over. The first worker tells what
+    /// its fuzzer is doing.
+    fn run_worker<F: Fuzzer>(self: &Arc<Self>, runner: &mut MainRunner<F>, first: bool) {
+        let mut fuzz_loop = StoppableLoop::new(FuzzLoop {
+            runner,
+            campaign: self.clone(),
+            execs: 0,
+            reports_phase: first,
+            retry: None,
+        });
+        {
+            let mut stops = self.stops.lock().unwrap();
+            if stops.stopped {
+                return;
+            }
+            stops.workers.push(Box::new(fuzz_loop.get_stop()));
+        }
+        if fuzz_loop.run().is_some() {
+            // The campaign is over for this worker, so it is for the others.
+            self.stop();
+        }
+    }
+
+    /// Count a finished execution, unless other workers spent the budget
+    /// already. Returns the number of inputs so far if it counted.
+    fn count_exec(&self) -> Option<u64> {
+        let mut report = self.report.lock().unwrap();
+        if self.budget.is_spent(report.execs + report.skipped_inputs) {
+            return None;
+        }
+        report.execs += 1;
+        Some(report.execs + report.skipped_inputs)
+    }
+
+    /// Whether the campaign is over for a worker whose input was not run:
+    /// the budget is spent, or the target cannot be run at all. Inputs skipped
+    /// as too large spend the budget like executions, or a run where no input
+    /// fits would never end.
+    fn is_over(&self) -> bool {
+        let report = self.report.lock().unwrap();
+        report.error.is_some() || self.budget.is_spent(report.execs + report.skipped_inputs)
+    }
+
+    /// End the campaign with an error, unless it already has one.
+    fn fail(&self, error: String) {
+        self.report.lock().unwrap().error.get_or_insert(error);
+    }
+
+    fn report(&self, elapsed: Duration, coverage: Option<&Coverage>) -> Report {
+        let mut report = std::mem::take(&mut *self.report.lock().unwrap());
+        let buckets = std::mem::take(&mut *self.buckets.lock().unwrap());
+        report.elapsed = elapsed;
+        report.interrupted = self.interrupted.is_raised();
+        report.coverage = coverage.map(|coverage| CoverageSummary {
+            covered_edges: coverage.covered_edges(),
+            interesting_execs: coverage.interesting_execs(),
+        });
+        report.total_crashes = buckets.total_hits();
+        report.crashes = buckets.into_crashes();
+        let hangs = std::mem::take(&mut *self.hangs.lock().unwrap());
+        report.total_hangs = hangs.total_hits();
+        report.hangs = hangs.into_crashes();
+        let out_of_memory = std::mem::take(&mut *self.out_of_memory.lock().unwrap());
+        report.total_out_of_memory = out_of_memory.total_hits();
+        report.out_of_memory = out_of_memory.into_crashes();
+        report
+    }
+}
+
+/// One iteration of this loop generates an input, runs the target with it and
+/// records the result in the campaign.
+struct FuzzLoop<'a, F: Fuzzer> {
+    runner: &'a mut MainRunner<F>,
+    campaign: Arc<Campaign>,
+    /// Executions of this worker alone.
+    execs: u64,
+    /// Whether to keep the campaign's phase up to date.
+    reports_phase: bool,
+    /// An input to run again, as the fork server died while running it.
+    retry: Option<Vec<u8>>,
+}
+
+impl<F: Fuzzer> LoopAction for FuzzLoop<'_, F> {
+    type Stop = Arc<Execution>;
+    /// `None` if the input was skipped.
+    type Wait = Option<Pending>;
+    type Output = ();
+
+    fn stop(execution: &Self::Stop) {
+        execution.was_stopped.raise();
+        // The child might have already exited, which is fine.
+        if let Some(child) = &execution.child {
+            child.kill();
+        }
+    }
+
+    fn start(&mut self) -> (Self::Stop, Self::Wait) {
+        if let Some(coverage) = &mut self.runner.coverage {
+            coverage.reset();
+        }
+        let input = match self.retry.take() {
+            Some(input) => input,
+            None => {
+                let input = self.runner.fuzzer.generate_input();
+                match self.runner.target.delivered(&input) {
+                    Delivered::Whole => (),
+                    Delivered::TruncatedAtNul => {
+                        self.campaign.report.lock().unwrap().truncated_inputs += 1
+                    }
+                    Delivered::TooLarge => {
+                        // Like an execution that is not run, so the loop can
+                        // be stopped even if no input fits.
+                        self.campaign.report.lock().unwrap().skipped_inputs += 1;
+                        let execution = Arc::new(Execution {
+                            child: None,
+                            was_stopped: Flag::default(),
+                        });
+                        return (execution, None);
+                    }
+                }
+                input
+            }
+        };
+        if self.reports_phase {
+            *self.campaign.phase.lock().unwrap() = self.runner.fuzzer.phase();
+        }
+        let (child, stderr) = match self.start_child(&input) {
+            Ok(started) => started,
+            Err(e) => {
+                let error = format!("Could not run the target: {}", e);
+                if self.execs > 0 && is_transient(&e) {
+                    // Like running out of processes for a moment. The input
+                    // is dropped, as an execution that does not count.
+                    eprintln!("{}", error);
+                } else {
+                    // Like a mistyped command, which would never start.
+                    self.campaign.fail(error);
                 }
-                WaitWithTimeoutResult::Timeout => (),
+                let execution = Arc::new(Execution {
+                    child: None,
+                    was_stopped: Flag::default(),
+                });
+                return (execution, None);
+            }
+        };
+        let execution = Arc::new(Execution {
+            child: Some(child.clone()),
+            was_stopped: Flag::default(),
+        });
+        let pending = Pending {
+            execution: execution.clone(),
+            child,
+            input,
+            stderr,
+            start_time: Instant::now(),
+        };
+        (execution, Some(pending))
+    }
+
+    fn wait(&mut self, pending: Self::Wait) -> Option<Self::Output> {
+        let Some(Pending {
+            execution,
+            child,
+            input,
+            stderr,
+            start_time,
+        }) = pending
+        else {
+            // The input was skipped, which spends the budget too.
+            return self.campaign.is_over().then_some(());
+        };
+        let result = wait_with_timeout(
+            child,
+            self.runner.single_execution_timeout,
+            &self.runner.delayer,
+        );
+        let duration = start_time.elapsed();
+        let campaign = self.campaign.clone();
+        if execution.was_stopped.is_raised() || campaign.interrupted.is_raised() {
+            // We killed it ourselves to stop. This execution does not count.
+            return None;
+        }
+        let result = match result {
+            Ok(result) => result,
+            Err(e) if stderr.is_none() && self.runner.harness.is_none() => {
+                // The fork server died with the input. Run it again without.
+                self.runner.drop_fork_server(e);
+                self.retry = Some(input);
+                return None;
+            }
+            Err(e) => {
+                eprintln!("Could not wait for the target: {}", e);
+                return None;
             }
+        };
+        let Some(total_execs) = campaign.count_exec() else {
+            // Other workers spent the budget already.
+            return Some(());
+        };
+        self.execs += 1;
+        let exit = match result {
+            WaitWithTimeoutResult::Finished(exit_status) => ExitKind::from(exit_status),
+            WaitWithTimeoutResult::Timeout => ExitKind::Timeout,
+        };
+        let new_coverage = self.runner.coverage.as_mut().map(|coverage| {
+            let found_new_coverage = coverage.update();
+            if self.execs == 1 && !coverage.saw_any() {
+                eprintln!(
+                    "Warning: the target did not report any coverage. Was it built with \
+                     -fsanitize-coverage and linked with resources/coverage/sancov_runtime.c?"
+                );
+            }
+            if found_new_coverage {
+                campaign.emit(&Event::NewCoverage {
+                    input: &input,
+                    covered_edges: coverage.covered_edges(),
+                });
+            }
+            found_new_coverage
+        });
+        let mut new_crash = false;
+        let mut is_crash = false;
+        if exit.is_crash() {
+            let stderr = match stderr {
+                Some(reader) => join_reader(reader),
+                None => self.runner.server_stderr(),
+            };
+            let stderr = String::from_utf8_lossy(&stderr.unwrap_or_default()).into_owned();
+            let memory_limited = self.runner.target.mem_limit.is_some();
+            let buckets = if classify::is_out_of_memory(exit, &stderr, memory_limited) {
+                Some(&campaign.out_of_memory)
+            } else if self.runner.crash_policy.is_crash(exit, &stderr) {
+                is_crash = true;
+                Some(&campaign.buckets)
+            } else {
+                campaign.report.lock().unwrap().ignored_failures += 1;
+                None
+            };
+            if let Some(buckets) = buckets {
+                let crash_dir = self.runner.crash_dir.as_ref();
+                let new_finding =
+                    campaign.keep(buckets, crash_dir, input.clone(), exit, stderr, duration);
+                new_crash = is_crash && new_finding;
+            }
+        } else if exit == ExitKind::Timeout {
+            campaign.report.lock().unwrap().timeouts += 1;
+            if self.runner.report_hangs {
+                self.confirm_hang(&input);
+            }
+        }
+        self.runner.fuzzer.observe(&Observation {
+            input: &input,
+            exit,
+            duration,
+            new_coverage,
+            new_crash,
+        });
+        if is_crash && campaign.stop_on_first_crash {
+            return Some(());
         }
+        campaign.budget.is_spent(total_execs).then_some(())
+    }
+}
+
+impl<F: Fuzzer> FuzzLoop<'_, F> {
+    /// Run the target with the input in the harness, the fork server, or a
+    /// process of its own, whose stderr is read in the background.
+    fn start_child(&mut self, input: &[u8]) -> io::Result<(Arc<dyn Running>, Option<Reader>)> {
+        let runner = &mut *self.runner;
+        if let Some(harness) = &mut runner.harness {
+            return Ok((Arc::new(harness.run(input)?), None));
+        }
+        if let Some(child) = runner.fork(input) {
+            return Ok((Arc::new(child), None));
+        }
+        let child = spawn(&runner.target, input, &mut runner.input_file, Capture::Stderr)?;
+        let stderr = read_in_background(child.take_stderr());
+        Ok((Arc::new(child), Some(stderr)))
+    }
+
+    /// Run an input that timed out again with a longer timeout, and keep it
+    /// as a hang if it times out again.
+    fn confirm_hang(&mut self, input: &[u8]) {
+        let timeout = self.runner.single_execution_timeout * HANG_CONFIRMATION_FACTOR;
+        let result = match self.runner.execute(input, timeout) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Could not confirm a hang: {}", e);
+                return;
+            }
+        };
+        if result.exit != ExitKind::Timeout {
+            return;
+        }
+        self.campaign.keep(
+            &self.campaign.hangs,
+            self.runner.crash_dir.as_ref(),
+            input.to_vec(),
+            result.exit,
+            result.stderr,
+            result.duration,
+        );
+    }
+}
+
+/// Save a finding to the crash directory, unless it is a duplicate, returning
+/// where it was saved. A smaller input of a bucket replaces the previous one.
+fn save_crash(crash_dir: &CrashDir, bucketed: &Bucketed, crash: &Crash) -> Option<PathBuf> {
+    if let Bucketed::Smaller { previous_input } = bucketed {
+        if let Err(e) = crash_dir.remove(crash.kind, previous_input) {
+            eprintln!("Could not remove replaced {}: {}", crash.kind.name(), e);
+        }
+    }
+    if *bucketed == Bucketed::Duplicate {
+        return None;
+    }
+    match crash_dir.save(crash) {
+        Ok(path) => {
+            println!("Saved {} ({}) to {:?}", crash.kind.name(), crash.exit, path);
+            Some(path)
+        }
+        Err(e) => {
+            eprintln!("Could not save {}: {}", crash.kind.name(), e);
+            None
+        }
+    }
+}
+
+/// Print the input of the first crash of a run that stopped on it.
+fn print_first_crash(report: &Report) {
+    match report.crashes.first() {
+        Some(crash) => println!(
+            "Execution succeeded. Output: '{}'",
+            InputFoundPrinter(&crash.input)
+        ),
+        None => println!("No crash found.\n{}", report),
+    }
+}
+
+impl<F: Fuzzer> MainRunner<F> {
+    fn fuzz(&mut self, budget: Budget, stop_on_first_crash: bool) -> Report {
+        let events = std::mem::take(&mut self.event_listeners);
+        let campaign = Campaign::new(budget, stop_on_first_crash, events);
+        campaign.emit(&Event::Started { workers: 1 });
+        let cancel_time_limit = campaign.stop_after_time_limit();
+        let stop_handle = self.stop_handle.clone();
+        let mut listeners = std::mem::take(&mut self.status_listeners);
+        campaign.run_watched(&mut listeners, || {
+            stop_handle.run(&campaign, || campaign.run_worker(self, true))
+        });
+        self.status_listeners = listeners;
+        if let Some(cancel_time_limit) = cancel_time_limit {
+            cancel_time_limit();
+        }
+        let report = campaign.report(campaign.start_time.elapsed(), self.coverage.as_ref());
+        campaign.emit(&Event::Stopped { report: &report });
+        self.event_listeners = campaign.take_event_listeners();
+        report
+    }
+
+    /// Fuzz until the first crash, or until the budget is spent (or the run
+    /// is stopped).
+    pub fn run_until_crash(&mut self, budget: Budget) -> Report {
+        self.fuzz(budget, true)
+    }
+
+    /// Keep fuzzing after crashes until the budget is spent (or the run is
+    /// stopped), collecting the smallest input of every kind of crash along
+    /// the way.
+    pub fn run_continuous(&mut self, budget: Budget) -> Report {
+        self.fuzz(budget, false)
     }
 }
 
 impl<T: Fuzzer + Send> Runner for MainRunner<T> {
     fn run(&mut self) {
-        let input_found = self.search_for_input();
-        println!(
-            "Execution succeeded. Output: '{}'",
-            InputFoundPrinter(input_found)
-        )
+        print_first_crash(&self.run_until_crash(Budget::default()));
+    }
+
+    /// Fails once the stop handle stopped, as the target was killed.
+    fn run_with_input(&mut self, input: &[u8]) -> Result<ProgramResult, String> {
+        let result = self
+            .execute(input, self.single_execution_timeout)
+            .map_err(|e| format!("could not run {:?}: {}", self.target.executable, e))?;
+        if self.stop_handle.is_stopped() {
+            return Err("stopped".to_string());
+        }
+        Ok(result)
     }
 
-    fn run_with_input(&mut self, _input: &[u8]) -> Result<ProgramResult, String> {
-        todo!()
+    fn is_crash(&self, result: &ProgramResult) -> bool {
+        self.crash_policy.is_crash(result.exit, &result.stderr)
     }
 }
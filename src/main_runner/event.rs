@@ -0,0 +1,23 @@
+//! What happens during a fuzzing run, as it happens, for event logs.
+
+use super::Report;
+use crate::dedup::Crash;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// A run started with this many workers.
+    Started { workers: usize },
+    /// A new kind of crash, hang or out-of-memory failure was found, and
+    /// maybe saved to the crash directory.
+    Found {
+        finding: &'a Crash,
+        saved_to: Option<&'a Path>,
+    },
+    /// An input reached new coverage.
+    NewCoverage { input: &'a [u8], covered_edges: usize },
+    /// The run is over.
+    Stopped { report: &'a Report },
+}
+
+pub(crate) type EventListener = Box<dyn FnMut(&Event) + Send>;
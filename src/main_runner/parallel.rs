@@ -0,0 +1,107 @@
+//! Running several workers on the same target at once.
+
+use super::{
+    print_first_crash, Budget, Campaign, Event, EventListener, MainRunner, Report, Status,
+    StatusListener, StopHandle,
+};
+use crate::fuzzer::Fuzzer;
+use std::{thread, time::Duration};
+
+/// Runs several `MainRunner`s at once, each on its own thread. They share the
+/// crashes they find, their statistics, their coverage and the budget. The
+/// first crash (unless running continuously) or the end of the budget stops
+/// all of them.
+pub struct ParallelRunner<F: Fuzzer> {
+    workers: Vec<MainRunner<F>>,
+    status_listeners: Vec<StatusListener>,
+    event_listeners: Vec<EventListener>,
+    stop_handle: StopHandle,
+}
+
+impl<F: Fuzzer + Send> ParallelRunner<F> {
+    /// The workers should run the same target, but with fuzzers that
+    /// generate different inputs (like `MainFuzzer`s with different seeds).
+    pub fn new(mut workers: Vec<MainRunner<F>>) -> Self {
+        assert!(!workers.is_empty(), "need at least one worker");
+        if let Some((first, others)) = workers.split_first_mut() {
+            if let Some(coverage) = &first.coverage {
+                for other in others {
+                    if let Some(other_coverage) = &mut other.coverage {
+                        other_coverage.share_progress_with(coverage);
+                    }
+                }
+            }
+        }
+        Self {
+            workers,
+            status_listeners: Vec::new(),
+            event_listeners: Vec::new(),
+            stop_handle: StopHandle::default(),
+        }
+    }
+
+    /// Let this handle stop every worker from another thread.
+    pub fn set_stop_handle(&mut self, stop_handle: StopHandle) {
+        self.stop_handle = stop_handle;
+    }
+
+    /// Like `MainRunner::add_status_listener`, for the runs of every worker.
+    pub fn add_status_listener(
+        &mut self,
+        interval: Duration,
+        listener: impl FnMut(&Status) + Send + 'static,
+    ) {
+        self.status_listeners.push(StatusListener {
+            interval,
+            listener: Box::new(listener),
+        });
+    }
+
+    /// Like `MainRunner::add_event_listener`, for the runs of every worker.
+    pub fn add_event_listener(&mut self, listener: impl FnMut(&Event) + Send + 'static) {
+        self.event_listeners.push(Box::new(listener));
+    }
+
+    fn fuzz(&mut self, budget: Budget, stop_on_first_crash: bool) -> Report {
+        let events = std::mem::take(&mut self.event_listeners);
+        let campaign = Campaign::new(budget, stop_on_first_crash, events);
+        campaign.emit(&Event::Started {
+            workers: self.workers.len(),
+        });
+        let cancel_time_limit = campaign.stop_after_time_limit();
+        let workers = &mut self.workers;
+        campaign.run_watched(&mut self.status_listeners, || {
+            self.stop_handle.run(&campaign, || {
+                thread::scope(|scope| {
+                    for (i, worker) in workers.iter_mut().enumerate() {
+                        let campaign = &campaign;
+                        scope.spawn(move || campaign.run_worker(worker, i == 0));
+                    }
+                })
+            })
+        });
+        if let Some(cancel_time_limit) = cancel_time_limit {
+            cancel_time_limit();
+        }
+        let coverage = self.workers[0].coverage.as_ref();
+        let report = campaign.report(campaign.start_time.elapsed(), coverage);
+        campaign.emit(&Event::Stopped { report: &report });
+        self.event_listeners = campaign.take_event_listeners();
+        report
+    }
+
+    /// Fuzz until any worker finds a crash, and print its input.
+    pub fn run(&mut self) {
+        print_first_crash(&self.run_until_crash(Budget::default()));
+    }
+
+    /// Like `MainRunner::run_until_crash`, with every worker.
+    pub fn run_until_crash(&mut self, budget: Budget) -> Report {
+        self.fuzz(budget, true)
+    }
+
+    /// Like `MainRunner::run_continuous`, with every worker.
+    pub fn run_continuous(&mut self, budget: Budget) -> Report {
+        self.fuzz(budget, false)
+    }
+}
@@ -0,0 +1,157 @@
+//! What a fuzzing run found, and the limits it was given.
+
+use super::InputFoundPrinter;
+use crate::dedup::Crash;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// Limits on how long a fuzzing run may keep going. A run with no limits only
+/// stops when it is stopped from the outside.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub max_time: Option<Duration>,
+    pub max_execs: Option<u64>,
+}
+
+impl Budget {
+    pub fn is_spent(&self, execs: u64) -> bool {
+        self.max_execs.is_some_and(|max_execs| execs >= max_execs)
+    }
+}
+
+/// How much of the target a coverage-guided run reached.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CoverageSummary {
+    pub covered_edges: usize,
+    /// How many executions found new coverage.
+    pub interesting_execs: usize,
+}
+
+/// The end-of-run summary of a fuzzing run.
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    pub execs: u64,
+    pub timeouts: u64,
+    pub elapsed: Duration,
+    /// The number of crashing executions, including duplicates.
+    pub total_crashes: u64,
+    /// One crash per bucket of similar crashes, in the order found.
+    pub crashes: Vec<Crash>,
+    /// Confirmed hangs, including duplicates. Only for runs that report hangs.
+    pub total_hangs: u64,
+    /// One hang per bucket of similar hangs, in the order found.
+    pub hangs: Vec<Crash>,
+    /// Executions that ran out of memory, including duplicates. They are not
+    /// counted as crashes.
+    pub total_out_of_memory: u64,
+    /// One input per bucket of similar out-of-memory failures.
+    pub out_of_memory: Vec<Crash>,
+    /// Only for runs with coverage enabled.
+    pub coverage: Option<CoverageSummary>,
+    /// Inputs the target only saw up to their first NUL byte, because they
+    /// were passed in an argument or environment variable.
+    pub truncated_inputs: u64,
+    /// Inputs that were not run at all, because they were too large for an
+    /// argument or environment variable.
+    pub skipped_inputs: u64,
+    /// Executions where the target failed in a way that the crash policy
+    /// does not count as a crash, like an ignored exit code.
+    pub ignored_failures: u64,
+    /// Whether the run was stopped from the outside (see `StopHandle`).
+    pub interrupted: bool,
+    /// Why the run ended early, like a target that could not be started.
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn execs_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.execs as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Fuzzing {} after {:.2}s and {} executions ({:.1} execs/sec).",
+            if self.error.is_some() {
+                "failed"
+            } else if self.interrupted {
+                "stopped"
+            } else {
+                "finished"
+            },
+            self.elapsed.as_secs_f64(),
+            self.execs,
+            self.execs_per_sec(),
+        )?;
+        writeln!(f, "Timeouts: {}", self.timeouts)?;
+        if self.truncated_inputs > 0 || self.skipped_inputs > 0 {
+            writeln!(
+                f,
+                "Inputs cut short at a NUL byte: {}, skipped as too large: {}",
+                self.truncated_inputs, self.skipped_inputs
+            )?;
+        }
+        if self.ignored_failures > 0 {
+            writeln!(f, "Failures not counted as crashes: {}", self.ignored_failures)?;
+        }
+        if let Some(coverage) = &self.coverage {
+            writeln!(
+                f,
+                "Coverage: {} edges, found by {} inputs",
+                coverage.covered_edges, coverage.interesting_execs
+            )?;
+        }
+        write!(
+            f,
+            "Crashes found: {} ({} unique)",
+            self.total_crashes,
+            self.crashes.len()
+        )?;
+        write_findings(f, &self.crashes)?;
+        if self.total_hangs > 0 {
+            write!(
+                f,
+                "\nHangs found: {} ({} unique)",
+                self.total_hangs,
+                self.hangs.len()
+            )?;
+            write_findings(f, &self.hangs)?;
+        }
+        if self.total_out_of_memory > 0 {
+            write!(
+                f,
+                "\nOut of memory: {} ({} unique)",
+                self.total_out_of_memory,
+                self.out_of_memory.len()
+            )?;
+            write_findings(f, &self.out_of_memory)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_findings(f: &mut Formatter, findings: &[Crash]) -> fmt::Result {
+    for (i, finding) in findings.iter().enumerate() {
+        write!(
+            f,
+            "\n  [{}] {}, seen {} times: '{}'",
+            i + 1,
+            finding.exit,
+            finding.hits,
+            InputFoundPrinter(&finding.input)
+        )?;
+        if let Some(report) = &finding.sanitizer {
+            write!(f, "\n      {}", report)?;
+        }
+    }
+    Ok(())
+}
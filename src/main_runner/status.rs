@@ -0,0 +1,49 @@
+//! How a fuzzing run is going while it runs, for status displays.
+
+use crate::fuzzer::Phase;
+use std::time::Duration;
+
+/// A snapshot of a fuzzing run that is still going.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Status {
+    pub elapsed: Duration,
+    pub execs: u64,
+    /// The number of crashing executions, including duplicates.
+    pub total_crashes: u64,
+    /// The number of distinct crashes.
+    pub unique_crashes: usize,
+    pub timeouts: u64,
+    /// Confirmed hangs, including duplicates, and the number of distinct
+    /// ones. Only for runs that report hangs.
+    pub total_hangs: u64,
+    pub unique_hangs: usize,
+    /// Executions that ran out of memory, including duplicates, and the
+    /// number of distinct ones.
+    pub total_out_of_memory: u64,
+    pub unique_out_of_memory: usize,
+    /// What the fuzzer (of the first worker) is doing, if it tells.
+    pub phase: Option<Phase>,
+    /// How long ago the last new crash, hang or out-of-memory failure was
+    /// found, if any was.
+    pub since_last_finding: Option<Duration>,
+    /// Whether the run is over. The last status of every run is.
+    pub done: bool,
+}
+
+impl Status {
+    pub fn execs_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.execs as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Something to call with the status of every run, every `interval` and
+/// once at the end.
+pub(crate) struct StatusListener {
+    pub interval: Duration,
+    pub listener: Box<dyn FnMut(&Status) + Send>,
+}
@@ -0,0 +1,156 @@
+//! Test-case minimisation: shrinking a crashing input down to the part that
+//! matters, using delta debugging.
+//!
+//! First whole chunks of the input are removed, ddmin style, starting with
+//! halves and going down to single bytes. Then every remaining byte that can
+//! be is replaced with a plain `'0'`. A candidate is only kept if it crashes
+//! the target in the same way (with the same `CrashSignature`) as the
+//! original input.
+
+use crate::dedup::{stable_hash, CrashSignature};
+use crate::runner::Runner;
+use std::collections::HashSet;
+
+/// The byte we try to replace every other byte with, like AFL's tmin does.
+const SIMPLE_BYTE: u8 = b'0';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimized {
+    pub input: Vec<u8>,
+    pub signature: CrashSignature,
+    /// How many times the target was executed while minimising.
+    pub execs: u64,
+}
+
+struct Minimizer<'a, R: Runner> {
+    runner: &'a mut R,
+    signature: CrashSignature,
+    /// Hashes of the candidates already run, not the candidates themselves,
+    /// which would add up to the size of the input squared. On the rare
+    /// collision we skip a candidate, and only shrink a little less.
+    tried: HashSet<u64>,
+    execs: u64,
+}
+
+impl<R: Runner> Minimizer<'_, R> {
+    /// Does this candidate still crash the same way?
+    fn reproduces(&mut self, candidate: &[u8]) -> Result<bool, String> {
+        if !self.tried.insert(stable_hash(candidate)) {
+            return Ok(false);
+        }
+        self.execs += 1;
+        let result = self.runner.run_with_input(candidate)?;
+        Ok(self.runner.is_crash(&result)
+            && CrashSignature::new(result.exit, &result.stderr) == self.signature)
+    }
+
+    fn remove_chunks(&mut self, mut input: Vec<u8>) -> Result<Vec<u8>, String> {
+        let mut chunks = 2;
+        while input.len() >= 2 {
+            let chunk_len = input.len().div_ceil(chunks);
+            let mut removed_one = false;
+            for start in (0..input.len()).step_by(chunk_len) {
+                let end = (start + chunk_len).min(input.len());
+                let candidate = [&input[..start], &input[end..]].concat();
+                if self.reproduces(&candidate)? {
+                    input = candidate;
+                    chunks = (chunks - 1).max(2);
+                    removed_one = true;
+                    break;
+                }
+            }
+            if removed_one {
+                continue;
+            }
+            if chunks >= input.len() {
+                break;
+            }
+            chunks = (chunks * 2).min(input.len());
+        }
+        // ddmin never tries the empty input.
+        if input.len() == 1 && self.reproduces(&[])? {
+            input.clear();
+        }
+        Ok(input)
+    }
+
+    fn simplify_bytes(&mut self, mut input: Vec<u8>) -> Result<Vec<u8>, String> {
+        for i in 0..input.len() {
+            if input[i] == SIMPLE_BYTE {
+                continue;
+            }
+            let mut candidate = input.clone();
+            candidate[i] = SIMPLE_BYTE;
+            if self.reproduces(&candidate)? {
+                input = candidate;
+            }
+        }
+        Ok(input)
+    }
+}
+
+/// Shrink a crashing input for as long as it keeps crashing the target in
+/// the same way. Fails if the input does not crash the target to begin with.
+pub fn minimize(runner: &mut impl Runner, input: &[u8]) -> Result<Minimized, String> {
+    let result = runner.run_with_input(input)?;
+    if !runner.is_crash(&result) {
+        return Err(format!("the input does not crash the target ({})", result.exit));
+    }
+    let mut minimizer = Minimizer {
+        runner,
+        signature: CrashSignature::new(result.exit, &result.stderr),
+        tried: HashSet::from([stable_hash(input)]),
+        execs: 1,
+    };
+    let input = minimizer.remove_chunks(input.to_vec())?;
+    let input = minimizer.simplify_bytes(input)?;
+    Ok(Minimized {
+        input,
+        signature: minimizer.signature,
+        execs: minimizer.execs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{ExitKind, ProgramResult};
+    use std::time::Duration;
+
+    /// Pretends to be a program that crashes when the function says so.
+    struct Crashes(fn(&[u8]) -> bool);
+
+    impl Runner for Crashes {
+        fn run(&mut self) {}
+
+        fn run_with_input(&mut self, input: &[u8]) -> Result<ProgramResult, String> {
+            Ok(ProgramResult {
+                stdout: Vec::new(),
+                stderr: String::new(),
+                exit: if (self.0)(input) { ExitKind::Signal(11) } else { ExitKind::Code(0) },
+                duration: Duration::ZERO,
+            })
+        }
+    }
+
+    #[test]
+    fn minimizes_to_the_crashing_bytes() {
+        let input = b"https://example.com/?q=X&page=Y#top";
+        let mut runner = Crashes(|input| input.contains(&b'X') && input.contains(&b'Y'));
+        let minimized = minimize(&mut runner, input).unwrap();
+        assert_eq!(minimized.input, b"XY");
+    }
+
+    #[test]
+    fn simplifies_bytes_that_can_not_be_removed() {
+        let mut runner = Crashes(|input| input.len() >= 3);
+        let minimized = minimize(&mut runner, b"a:b/c").unwrap();
+        assert_eq!(minimized.input, b"000");
+    }
+
+    #[test]
+    fn rejects_non_crashing_input() {
+        let mut runner = Crashes(|input| input.contains(&b'X'));
+        assert!(minimize(&mut runner, b"hello").is_err());
+    }
+}
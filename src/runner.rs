@@ -1,32 +1,94 @@
+use crate::delay::Delayer;
 use crate::fuzzer::Fuzzer;
-use std::{path::PathBuf, time::Duration};
+use crate::main_runner::{self, StopHandle};
+use crate::target::Target;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+    process::ExitStatus,
+    time::Duration,
+};
+
+/// How an execution of the program ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitKind {
+    /// The program exited on its own with this exit code.
+    Code(i32),
+    /// The program was killed by this signal.
+    Signal(i32),
+    /// The program did not finish in time, and we killed it.
+    Timeout,
+}
+
+impl ExitKind {
+    pub fn is_success(&self) -> bool {
+        matches!(self, ExitKind::Code(0))
+    }
+
+    /// Did the program fail on its own (as opposed to us killing it)?
+    pub fn is_crash(&self) -> bool {
+        !self.is_success() && *self != ExitKind::Timeout
+    }
+}
+
+impl From<ExitStatus> for ExitKind {
+    fn from(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return ExitKind::Signal(signal);
+            }
+        }
+        // Without a signal, the process must have exited with a code.
+        ExitKind::Code(status.code().unwrap_or(-1))
+    }
+}
+
+impl Display for ExitKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ExitKind::Code(code) => write!(f, "exit code {}", code),
+            ExitKind::Signal(signal) => write!(f, "signal {}", signal),
+            ExitKind::Timeout => write!(f, "timeout"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct ProgramResult {
     pub stdout: Vec<u8>,
     pub stderr: String,
-    pub exit_code: i32,
+    pub exit: ExitKind,
+    /// How long the program ran for.
+    pub duration: Duration,
 }
 
 pub trait Runner {
     fn run(&mut self);
-    // TODO: Remove this?
+    /// Run the program once with the given input, and wait for it to finish.
     fn run_with_input(&mut self, input: &[u8]) -> Result<ProgramResult, String>;
+
+    /// Does this result count as a crash? By default every failure does.
+    fn is_crash(&self, result: &ProgramResult) -> bool {
+        result.exit.is_crash()
+    }
 }
 
-#[allow(dead_code)]
 pub struct DefaultRunner<F: Fuzzer> {
-    executable: PathBuf,
+    target: Target,
     timeout: Duration,
     fuzzer: F,
+    delayer: Delayer<Box<dyn FnOnce() + Send + 'static>>,
 }
 
 impl<T: Fuzzer> DefaultRunner<T> {
     pub fn new(executable: PathBuf, timeout: Duration, fuzzer: T) -> Self {
         Self {
-            executable,
+            target: Target::new(executable),
             timeout,
             fuzzer,
+            delayer: Delayer::new(),
         }
     }
 }
@@ -41,11 +103,15 @@ impl<T: Fuzzer> Runner for DefaultRunner<T> {
     }
 
     fn run_with_input(&mut self, input: &[u8]) -> Result<ProgramResult, String> {
-        // Placeholder for running the executable with input
-        Ok(ProgramResult {
-            stdout: input.to_vec(),
-            stderr: "".to_string(),
-            exit_code: 0,
-        })
+        let stop_handle = StopHandle::default();
+        main_runner::execute(
+            &self.target,
+            input,
+            &mut None,
+            self.timeout,
+            &self.delayer,
+            &stop_handle,
+        )
+        .map_err(|e| format!("could not run {:?}: {}", self.target.executable, e))
     }
 }
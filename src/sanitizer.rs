@@ -0,0 +1,387 @@
+//! Parsing of the reports sanitizers (ASan, UBSan, MSan, LSan, TSan and
+//! HWASan) write to stderr, for targets built with `-fsanitize=...`.
+//!
+//! A report starts with a headline naming the bug, like
+//! `==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x...`
+//! or `src/x.c:15:9: runtime error: signed integer overflow: ...`, followed by
+//! the stack trace of where it happened.
+
+use std::fmt::{self, Display, Formatter};
+
+/// How many frames from the top of the stack trace are kept.
+pub const TOP_FRAMES: usize = 3;
+
+/// Frames in the sanitizer runtime itself, like the `strcpy` interceptor, which
+/// say nothing about where the bug is.
+const RUNTIME_FRAME_PREFIXES: &[&str] = &[
+    "__asan",
+    "__msan",
+    "__lsan",
+    "__ubsan",
+    "__tsan",
+    "__hwasan",
+    "__sanitizer",
+    "__interceptor_",
+];
+
+/// The entry point of libFuzzer harnesses. Frames below it are whatever
+/// called the harness, like our supervisor, so they are left out.
+const HARNESS_ENTRY: &str = "LLVMFuzzerTestOneInput";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sanitizer {
+    Address,
+    UndefinedBehavior,
+    Memory,
+    Leak,
+    Thread,
+    HWAddress,
+}
+
+impl Sanitizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "AddressSanitizer",
+            Sanitizer::UndefinedBehavior => "UndefinedBehaviorSanitizer",
+            Sanitizer::Memory => "MemorySanitizer",
+            Sanitizer::Leak => "LeakSanitizer",
+            Sanitizer::Thread => "ThreadSanitizer",
+            Sanitizer::HWAddress => "HWAddressSanitizer",
+        }
+    }
+}
+
+/// Whether the bad memory access was a read or a write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// The memory access that triggered the report, like `READ of size 4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Access {
+    pub kind: AccessKind,
+    /// Unknown for wild accesses, like a SEGV.
+    pub size: Option<u64>,
+}
+
+/// What a sanitizer found, taken from its report.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SanitizerReport {
+    pub sanitizer: Sanitizer,
+    /// Like `heap-buffer-overflow`, `SEGV on unknown address` or
+    /// `signed-integer-overflow`.
+    pub bug_type: String,
+    pub access: Option<Access>,
+    /// The functions at the top of the stack trace, innermost first, without
+    /// those of the sanitizer runtime.
+    pub frames: Vec<String>,
+    /// The source location UBSan reports the error at, as `file:line:column`.
+    pub location: Option<String>,
+}
+
+impl Display for SanitizerReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.sanitizer.name(), self.bug_type)?;
+        if let Some(access) = &self.access {
+            let kind = match access.kind {
+                AccessKind::Read => "READ",
+                AccessKind::Write => "WRITE",
+            };
+            match access.size {
+                Some(size) => write!(f, " ({} of size {})", kind, size)?,
+                None => write!(f, " ({})", kind)?,
+            }
+        }
+        if !self.frames.is_empty() {
+            write!(f, " in {}", self.frames.join(" < "))?;
+        } else if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the first sanitizer report in stderr, if there is one.
+pub fn parse(stderr: &str) -> Option<SanitizerReport> {
+    let lines: Vec<&str> = stderr.lines().map(str::trim).collect();
+    let (start, (sanitizer, headline)) = lines
+        .iter()
+        .enumerate()
+        .find_map(|(i, line)| Some((i, headline(line)?)))?;
+    let mut report = SanitizerReport {
+        sanitizer,
+        bug_type: String::new(),
+        access: None,
+        frames: Vec::new(),
+        location: None,
+    };
+    match headline.split_once("runtime error: ") {
+        // `src/x.c:15:9: runtime error: signed integer overflow: 2147483647 * ...`
+        Some((location, message)) if sanitizer == Sanitizer::UndefinedBehavior => {
+            let location = location.trim_end_matches([':', ' ']);
+            report.location = (!location.is_empty()).then(|| location.to_string());
+            report.bug_type = ubsan_bug_type(message);
+        }
+        // UBSan reports the signals it handles like ASan does.
+        _ => report.bug_type = bug_type(headline).to_string(),
+    }
+
+    let mut in_trace = false;
+    let mut below_harness = false;
+    for line in &lines[start + 1..] {
+        if let Some(access) = access(line) {
+            report.access.get_or_insert(access);
+        }
+        if line.starts_with('#') {
+            in_trace = true;
+            if let Some(function) = frame_function(line) {
+                let is_runtime = RUNTIME_FRAME_PREFIXES
+                    .iter()
+                    .any(|prefix| function.starts_with(prefix));
+                if !is_runtime && !below_harness && report.frames.len() < TOP_FRAMES {
+                    report.frames.push(function.to_string());
+                }
+                below_harness |= function == HARNESS_ENTRY;
+            }
+        } else if in_trace {
+            // Only the first stack trace is where the bug happened. Later ones
+            // are about where the memory came from.
+            break;
+        }
+    }
+    Some(report)
+}
+
+/// The sanitizer and the rest of the line, if it is a report headline.
+fn headline(line: &str) -> Option<(Sanitizer, &str)> {
+    for sanitizer in [
+        Sanitizer::Address,
+        Sanitizer::UndefinedBehavior,
+        Sanitizer::Memory,
+        Sanitizer::Leak,
+        Sanitizer::Thread,
+        Sanitizer::HWAddress,
+    ] {
+        for level in ["ERROR: ", "WARNING: "] {
+            let prefix = format!("{}{}: ", level, sanitizer.name());
+            if let Some((_, rest)) = line.split_once(prefix.as_str()) {
+                return Some((sanitizer, rest));
+            }
+        }
+    }
+    // Targets may echo their input, so the words alone are not enough: UBSan
+    // puts the source location first.
+    let (location, _) = line.split_once(": runtime error: ")?;
+    is_source_location(location).then_some((Sanitizer::UndefinedBehavior, line))
+}
+
+/// Whether it looks like `src/x.c:15:9`.
+fn is_source_location(location: &str) -> bool {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let mut parts = location.rsplitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(column), Some(line), Some(file)) => {
+            !file.is_empty()
+                && !file.contains(char::is_whitespace)
+                && is_number(line)
+                && is_number(column)
+        }
+        _ => false,
+    }
+}
+
+/// The bug named in a headline with an `ERROR:` or `WARNING:` prefix, without
+/// the addresses:
+/// `heap-buffer-overflow on address 0x602...` is a `heap-buffer-overflow`.
+fn bug_type(headline: &str) -> &str {
+    let end = [" 0x", " ("]
+        .iter()
+        .filter_map(|marker| headline.find(marker))
+        .min()
+        .unwrap_or(headline.len());
+    let bug_type = headline[..end].trim();
+    let bug_type = bug_type.strip_suffix(" on address").unwrap_or(bug_type);
+    bug_type.strip_suffix(" on").unwrap_or(bug_type)
+}
+
+/// The bug named in a UBSan message, written like the `-fsanitize` check:
+/// `signed integer overflow: 2147483647 * 1000 ...` is a
+/// `signed-integer-overflow`.
+fn ubsan_bug_type(message: &str) -> String {
+    let message = message.split(':').next().unwrap_or(message);
+    message
+        .split_whitespace()
+        .take_while(|word| !word.starts_with(|c: char| c.is_ascii_digit() || c == '\''))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parse `READ of size 4 at 0x...` or `The signal is caused by a WRITE memory
+/// access.`
+fn access(line: &str) -> Option<Access> {
+    let line = line.rsplit("==").next().unwrap_or(line);
+    let kind_of = |word: &str| match word {
+        "READ" => Some(AccessKind::Read),
+        "WRITE" => Some(AccessKind::Write),
+        _ => None,
+    };
+    if let Some(rest) = line.strip_prefix("The signal is caused by a ") {
+        let kind = kind_of(rest.split_whitespace().next()?)?;
+        return Some(Access { kind, size: None });
+    }
+    let (kind, rest) = line.split_once(" of size ")?;
+    let size = rest.split_whitespace().next()?.parse().ok()?;
+    Some(Access {
+        kind: kind_of(kind)?,
+        size: Some(size),
+    })
+}
+
+/// Parse a frame, `#0 0x4f5a3c in main /src/main.c:12:5`, into the name of
+/// its function. Frames without one, like `#1 0x7f09 (/lib/libc.so.6+0x27)`,
+/// have none.
+pub fn frame_function(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('#')?;
+    let (index, rest) = rest.split_once(' ')?;
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (_, rest) = rest.split_once(" in ")?;
+    Some(rest.split_whitespace().next().unwrap_or(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From resources/benchmark/buffer_overflow.c, built with gcc.
+    const ASAN_OVERFLOW: &str = "\
+=================================================================
+==21534==ERROR: AddressSanitizer: stack-buffer-overflow on address 0x7fff4e0b37aa at pc 0x7f259aa602ca bp 0x7fff4e0b3760 sp 0x7fff4e0b2f10
+WRITE of size 100 at 0x7fff4e0b37aa thread T0
+    #0 0x7f259aa602c9 in __interceptor_strcpy ../../../../src/libsanitizer/asan/asan_interceptors.cpp:425
+    #1 0x55567cb54243 in vulnerable_function /src/buffer_overflow.c:6
+    #2 0x55567cb54375 in main /src/buffer_overflow.c:14
+    #3 0x7f259a845249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+    #4 0x7f259a845304 in __libc_start_main (/lib/x86_64-linux-gnu/libc.so.6+0x27304)
+
+Address 0x7fff4e0b37aa is located in stack of thread T0 at offset 42 in frame
+    #0 0x55567cb541c8 in vulnerable_function /src/buffer_overflow.c:4
+SUMMARY: AddressSanitizer: stack-buffer-overflow in __interceptor_strcpy
+==21534==ABORTING
+";
+
+    // From resources/benchmark/null_deref.c.
+    const ASAN_SEGV: &str = "\
+AddressSanitizer:DEADLYSIGNAL
+=================================================================
+==21560==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x55d8039ec34a bp 0x7ffe9ec26510 sp 0x7ffe9ec26480 T0)
+==21560==The signal is caused by a WRITE memory access.
+==21560==Hint: address points to the zero page.
+    #0 0x55d8039ec34a in main /src/null_deref.c:22
+    #1 0x7f41e1645249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+";
+
+    // From resources/benchmark/integer_overflow.c, with print_stacktrace=1.
+    const UBSAN: &str = "\
+/src/integer_overflow.c:15:9: runtime error: signed integer overflow: 2147483647 * 1000 cannot be represented in type 'int'
+    #0 0x5567242531de in main /src/integer_overflow.c:15
+    #1 0x7f092c445249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+";
+
+    // From a harness library run with --harness.
+    const ASAN_HARNESS: &str = "\
+==3756==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000832 at pc 0x7fbdb9848061 bp 0x7ffe318b3fe0 sp 0x7ffe318b3790
+WRITE of size 14 at 0x602000000832 thread T0
+    #0 0x7fbdb9848060 in __interceptor_memcpy ../../../../src/libsanitizer/sanitizer_common/sanitizer_common_interceptors.inc:827
+    #1 0x7fbdb9ed31d3 in LLVMFuzzerTestOneInput /tmp/harness.c:5
+    #2 0x564e08695a29 in fuzzer::harness::supervise::h097f904c4c8552d7 (/root/crate/target/release/fuzzer+0x4fa29)
+    #3 0x564e08695866 in fuzzer::harness::Supervisor::run::h4ae7dca4b5dead50 (/root/crate/target/release/fuzzer+0x4f866)
+";
+
+    const MSAN: &str = "\
+==4711==WARNING: MemorySanitizer: use-of-uninitialized-value
+    #0 0x4a0b2c in parse /src/x.c:7:7
+    #1 0x4a0c00 in main /src/x.c:12:3
+";
+
+    #[test]
+    fn parses_asan_reports() {
+        let report = parse(ASAN_OVERFLOW).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Address);
+        assert_eq!(report.bug_type, "stack-buffer-overflow");
+        let access = Access {
+            kind: AccessKind::Write,
+            size: Some(100),
+        };
+        assert_eq!(report.access, Some(access));
+        assert_eq!(report.frames, ["vulnerable_function", "main", "__libc_start_main"]);
+        assert_eq!(
+            report.to_string(),
+            "AddressSanitizer: stack-buffer-overflow (WRITE of size 100) in \
+             vulnerable_function < main < __libc_start_main"
+        );
+
+        let report = parse(ASAN_SEGV).unwrap();
+        assert_eq!(report.bug_type, "SEGV on unknown address");
+        let access = Access {
+            kind: AccessKind::Write,
+            size: None,
+        };
+        assert_eq!(report.access, Some(access));
+        assert_eq!(report.frames, ["main"]);
+
+        let double_free = "==1==ERROR: AddressSanitizer: attempting double-free on 0x6020 in thread T0:";
+        assert_eq!(parse(double_free).unwrap().bug_type, "attempting double-free");
+
+        let report = parse(ASAN_HARNESS).unwrap();
+        assert_eq!(report.frames, ["LLVMFuzzerTestOneInput"]);
+    }
+
+    #[test]
+    fn parses_ubsan_and_msan_reports() {
+        let report = parse(UBSAN).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::UndefinedBehavior);
+        assert_eq!(report.bug_type, "signed-integer-overflow");
+        assert_eq!(report.location.as_deref(), Some("/src/integer_overflow.c:15:9"));
+        assert_eq!(report.frames, ["main"]);
+        assert_eq!(report.access, None);
+
+        let without_trace = UBSAN.lines().next().unwrap();
+        let report = parse(without_trace).unwrap();
+        assert!(report.frames.is_empty());
+        assert_eq!(
+            report.to_string(),
+            "UndefinedBehaviorSanitizer: signed-integer-overflow at /src/integer_overflow.c:15:9"
+        );
+
+        let report = parse(MSAN).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Memory);
+        assert_eq!(report.bug_type, "use-of-uninitialized-value");
+        assert_eq!(report.frames, ["parse", "main"]);
+
+        let ubsan_segv = "==7==ERROR: UndefinedBehaviorSanitizer: SEGV on unknown address 0x000000000000 (pc 0x55 T0)";
+        let report = parse(ubsan_segv).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::UndefinedBehavior);
+        assert_eq!(report.bug_type, "SEGV on unknown address");
+        assert_eq!(report.location, None);
+
+        let tsan = "WARNING: ThreadSanitizer: data race (pid=9)";
+        assert_eq!(parse(tsan).unwrap().sanitizer, Sanitizer::Thread);
+        assert_eq!(parse(tsan).unwrap().bug_type, "data race");
+        let hwasan = "==3==ERROR: HWAddressSanitizer: tag-mismatch on address 0x0420 at pc 0x5";
+        assert_eq!(parse(hwasan).unwrap().sanitizer, Sanitizer::HWAddress);
+
+        assert_eq!(parse("Segmentation fault (core dumped)"), None);
+    }
+
+    #[test]
+    fn ignores_runtime_errors_without_a_source_location() {
+        assert_eq!(parse("Invalid input \"runtime error: oops\""), None);
+        assert_eq!(parse("Echo: x.c:1:2: runtime error: oops"), None);
+        assert_eq!(parse("x.c:15: runtime error: signed integer overflow"), None);
+        assert_eq!(parse("runtime error: division by zero"), None);
+    }
+}
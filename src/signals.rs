@@ -0,0 +1,44 @@
+//! Stopping the fuzzer cleanly on Ctrl-C (SIGINT) or SIGTERM.
+
+use crate::status_screen;
+use fuzzer::StopHandle;
+use std::{io, mem, process, ptr, thread};
+
+/// Block SIGINT and SIGTERM, and wait for them on a thread of their own
+/// instead. The first one stops the run through the handle, so it can still
+/// print its report. A second one kills what is still running and exits right
+/// away.
+///
+/// Must be called before any other thread is started, as only threads started
+/// afterwards inherit the blocked signals. Children of ours get the default
+/// signal mask back when they exec.
+pub fn stop_on_signals(stop_handle: StopHandle) -> io::Result<()> {
+    let signals = unsafe {
+        let mut signals: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        signals
+    };
+    let result = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+
+    thread::spawn(move || loop {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+            continue;
+        }
+        if stop_handle.is_stopped() {
+            eprintln!("Stopping right away.");
+            stop_handle.kill_executions();
+            status_screen::restore_terminal();
+            process::exit(128 + signal);
+        }
+        let name = if signal == libc::SIGINT { "SIGINT" } else { "SIGTERM" };
+        eprintln!("Got {}, stopping... (again to stop right away)", name);
+        stop_handle.stop();
+    });
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! The live status of a fuzzing run, at the bottom of the terminal.
+//!
+//! The bottom lines of the terminal are kept out of its scrolling region, so
+//! whatever else is printed during the run (like saved crashes) scrolls by
+//! above the status instead of mixing with it. The terminal gets all of its
+//! lines back when the run is over, or when the screen is dropped, like on a
+//! panic. Exiting right away must call `restore_terminal` first.
+
+use fuzzer::Status;
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// How often the status is redrawn.
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether a status screen took lines out of the scrolling region.
+static SHRUNK_SCROLLING_REGION: AtomicBool = AtomicBool::new(false);
+
+/// Give the whole terminal back to scrolling, if a status screen took lines
+/// out of it, before exiting without dropping the screen. The status is left
+/// where it is.
+pub fn restore_terminal() {
+    if SHRUNK_SCROLLING_REGION.swap(false, Ordering::SeqCst) {
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(b"\x1b7\x1b[r\x1b8");
+        let _ = stdout.flush();
+    }
+}
+
+pub struct StatusScreen {
+    /// The terminal height the scrolling region was set up for.
+    rows: u16,
+    /// How many lines the status takes.
+    height: u16,
+}
+
+impl StatusScreen {
+    /// A status screen, if stdout is a terminal.
+    pub fn new() -> Option<Self> {
+        io::stdout().is_terminal().then_some(Self { rows: 0, height: 0 })
+    }
+
+    /// Draw the status, or clear it for good once the run is over.
+    pub fn update(&mut self, status: &Status) {
+        if status.done {
+            self.clear();
+            return;
+        }
+        let lines = status_lines(status);
+        let height = lines.len() as u16;
+        let mut out = String::new();
+        let Some(rows) = terminal_rows().filter(|&rows| rows > height + 1) else {
+            return;
+        };
+        if rows != self.rows {
+            if self.rows == 0 {
+                // Make room at the bottom, scrolling up what is there.
+                out.push_str(&"\n".repeat(height as usize));
+                let _ = write!(out, "\x1b[{}A", height);
+            }
+            // Setting the scrolling region moves the cursor, so keep it.
+            let _ = write!(out, "\x1b7\x1b[1;{}r\x1b8", rows - height);
+            self.rows = rows;
+            self.height = height;
+            SHRUNK_SCROLLING_REGION.store(true, Ordering::SeqCst);
+        }
+        let top = rows - height + 1;
+        let _ = write!(out, "\x1b7\x1b[{};1H\x1b[J{}\x1b8", top, lines.join("\r\n"));
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// Give the status lines back to the scrolling region, empty.
+    fn clear(&mut self) {
+        if self.rows == 0 {
+            return;
+        }
+        let top = self.rows - self.height + 1;
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "\x1b7\x1b[{};1H\x1b[J\x1b[r\x1b8", top);
+        let _ = stdout.flush();
+        self.rows = 0;
+        SHRUNK_SCROLLING_REGION.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for StatusScreen {
+    /// Clear the status if the run did not get to, like when it panicked.
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+fn status_lines(status: &Status) -> Vec<String> {
+    let phase = match status.phase {
+        Some(phase) => phase.to_string(),
+        None => "-".to_string(),
+    };
+    let last_finding = match status.since_last_finding {
+        Some(since) => format!("{} ago", clock(since)),
+        None => "none yet".to_string(),
+    };
+    let mut findings = format!(
+        " crashes {} ({} unique)   timeouts {}",
+        status.total_crashes, status.unique_crashes, status.timeouts
+    );
+    if status.total_hangs > 0 {
+        let _ = write!(findings, "   hangs {} ({} unique)", status.total_hangs, status.unique_hangs);
+    }
+    if status.total_out_of_memory > 0 {
+        let _ = write!(
+            findings,
+            "   out of memory {} ({} unique)",
+            status.total_out_of_memory, status.unique_out_of_memory
+        );
+    }
+    vec![
+        format!(
+            " run time {}   execs {} ({:.0}/sec)   phase {}",
+            clock(status.elapsed),
+            status.execs,
+            status.execs_per_sec(),
+            phase
+        ),
+        findings,
+        format!(" last new finding {}", last_finding),
+    ]
+}
+
+/// A duration as h:mm:ss.
+fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn terminal_rows() -> Option<u16> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return None;
+    }
+    (size.ws_row > 0).then_some(size.ws_row)
+}
@@ -69,9 +69,7 @@ impl<A: LoopAction> StoppableLoop<A> {
 
     pub fn run(&mut self) -> Option<A::Output> {
         loop {
-            let Some(wait) = self.start_action() else {
-                return None;
-            };
+            let wait = self.start_action()?;
 
             let output = self.action.wait(wait);
             if self.state.lock().unwrap().is_stopped() {
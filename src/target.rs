@@ -0,0 +1,457 @@
+//! How to start the program being fuzzed, and how it gets its input.
+//!
+//! By default the input is written to the target's stdin. The target's
+//! arguments can instead contain a placeholder for the input, following AFL:
+//! - `@@` is replaced with the path of a temporary file holding the input,
+//! - `{input}` is replaced with the input itself.
+//!
+//! Or the input can be put in an environment variable of the target.
+//!
+//! The target gets default options for the sanitizers it may be built with,
+//! so that they report every bug the same way: by aborting, with a symbolized
+//! stack trace. Options the target is given, or inherits, are added after the
+//! defaults, so they win.
+//!
+//! Arguments and environment variables are C strings, so the target only sees
+//! the input up to its first NUL byte. They are also limited in size, and
+//! inputs that do not fit cannot be delivered at all.
+
+use std::{
+    env,
+    ffi::{CString, OsStr, OsString},
+    fs::{self, File},
+    io,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::{FileExt, OpenOptionsExt},
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Replaced with the path of a file holding the input.
+pub const FILE_PLACEHOLDER: &str = "@@";
+/// Replaced with the input itself.
+pub const ARGUMENT_PLACEHOLDER: &str = "{input}";
+/// The longest argument or environment variable (as `NAME=value`) Linux
+/// accepts, including the terminating NUL byte.
+pub const MAX_STRING_LEN: usize = 32 * 4096;
+/// The default runtime options of every sanitizer. Leak detection is off, as
+/// leaks are rarely what we are looking for and need ptrace to be found.
+pub const SANITIZER_DEFAULTS: &[(&str, &str)] = &[
+    ("ASAN_OPTIONS", "abort_on_error=1:symbolize=1:detect_leaks=0"),
+    (
+        "UBSAN_OPTIONS",
+        "halt_on_error=1:abort_on_error=1:print_stacktrace=1:symbolize=1",
+    ),
+    ("MSAN_OPTIONS", "abort_on_error=1:symbolize=1"),
+];
+
+/// How the input reaches the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputDelivery {
+    Stdin,
+    File,
+    Argument,
+    Env,
+}
+
+/// What happens to an input on its way to the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivered {
+    /// The target gets the whole input.
+    Whole,
+    /// The target only gets the input up to its first NUL byte.
+    TruncatedAtNul,
+    /// The input does not fit in an argument or environment variable.
+    TooLarge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub executable: PathBuf,
+    /// The target's arguments, which may contain an input placeholder.
+    pub args: Vec<OsString>,
+    /// Environment variables set for the target, on top of the ones it
+    /// inherits from us.
+    pub env: Vec<(OsString, OsString)>,
+    /// Start the target with only `env`, instead of inheriting ours.
+    pub clear_env: bool,
+    /// The environment variable to put the input in, instead of stdin or the
+    /// arguments.
+    pub input_env: Option<OsString>,
+    /// How many bytes of memory the target may use (as `RLIMIT_AS` and
+    /// `RLIMIT_DATA`).
+    pub mem_limit: Option<u64>,
+}
+
+impl Target {
+    pub fn new(executable: impl Into<PathBuf>) -> Self {
+        Self {
+            executable: executable.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            clear_env: false,
+            input_env: None,
+            mem_limit: None,
+        }
+    }
+
+    pub fn with_args<S: Into<OsString>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn set_env(&mut self, key: impl Into<OsString>, value: impl Into<OsString>) {
+        let key = key.into();
+        self.env.retain(|(existing, _)| *existing != key);
+        self.env.push((key, value.into()));
+    }
+
+    /// Do not pass our environment on to the target, so runs do not depend on
+    /// the machine they are on.
+    pub fn with_clear_env(mut self) -> Self {
+        self.clear_env = true;
+        self
+    }
+
+    /// Put the input in this environment variable of the target.
+    pub fn with_input_env(mut self, name: impl Into<OsString>) -> Self {
+        self.input_env = Some(name.into());
+        self
+    }
+
+    /// Limit the memory the target may use, so an input that makes it
+    /// allocate without bound makes allocations fail instead of taking down
+    /// the machine.
+    pub fn with_mem_limit(mut self, bytes: u64) -> Self {
+        self.mem_limit = Some(bytes);
+        self
+    }
+
+    /// How the input reaches the target: in the input environment variable
+    /// if there is one, otherwise decided by the placeholders in its
+    /// arguments. A file placeholder wins over an argument placeholder.
+    pub fn input_delivery(&self) -> InputDelivery {
+        if self.input_env.is_some() {
+            return InputDelivery::Env;
+        }
+        let has = |placeholder: &str| {
+            self.args
+                .iter()
+                .any(|arg| find(arg.as_bytes(), placeholder.as_bytes()).is_some())
+        };
+        if has(FILE_PLACEHOLDER) {
+            InputDelivery::File
+        } else if has(ARGUMENT_PLACEHOLDER) {
+            InputDelivery::Argument
+        } else {
+            InputDelivery::Stdin
+        }
+    }
+
+    /// What happens to this input on its way to the target.
+    pub fn delivered(&self, input: &[u8]) -> Delivered {
+        let c_string = until_nul(input);
+        let longest = match (self.input_delivery(), &self.input_env) {
+            (InputDelivery::Env, Some(name)) => name.len() + "=".len() + c_string.len(),
+            (InputDelivery::Argument, _) => self
+                .args
+                .iter()
+                .map(|arg| replace(arg, ARGUMENT_PLACEHOLDER, c_string).len())
+                .max()
+                .unwrap_or_default(),
+            _ => return Delivered::Whole,
+        };
+        if longest + 1 > MAX_STRING_LEN {
+            Delivered::TooLarge
+        } else if c_string.len() < input.len() {
+            Delivered::TruncatedAtNul
+        } else {
+            Delivered::Whole
+        }
+    }
+
+    /// A command that starts the target with this input in its arguments or
+    /// environment, if it takes it there. Its stdio is left for the caller to
+    /// set up. Fails for inputs that are too large to deliver.
+    ///
+    /// With file delivery, the input is written to `input_file`, which is
+    /// created the first time. Keep it for the next inputs, and until the
+    /// target exits.
+    pub(crate) fn command(
+        &self,
+        input: &[u8],
+        input_file: &mut Option<InputFile>,
+    ) -> io::Result<Command> {
+        if self.delivered(input) == Delivered::TooLarge {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the input is {} bytes, too large for an argument or environment variable",
+                    input.len()
+                ),
+            ));
+        }
+        // TODO: Can we limit Command::new to only absolute paths?
+        let mut command = Command::new(&self.executable);
+        if self.clear_env {
+            command.env_clear();
+        }
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        for (name, defaults) in SANITIZER_DEFAULTS {
+            command.env(name, self.sanitizer_options(name, defaults));
+        }
+        // A group of its own, so we can kill it along with its subprocesses.
+        command.process_group(0);
+        if let Some(bytes) = self.mem_limit {
+            // Safety: setrlimit is async-signal-safe, and nothing else is
+            // touched between fork and exec.
+            unsafe { command.pre_exec(move || limit_memory(bytes)) };
+        }
+        match self.input_delivery() {
+            InputDelivery::Stdin => {
+                command.args(&self.args);
+            }
+            InputDelivery::File => {
+                let input_file = match input_file {
+                    Some(input_file) => input_file,
+                    None => input_file.insert(InputFile::create()?),
+                };
+                input_file.write(input)?;
+                let path = input_file.path().as_os_str().as_bytes();
+                command.args(self.args.iter().map(|arg| replace(arg, FILE_PLACEHOLDER, path)));
+            }
+            InputDelivery::Argument => {
+                let input = until_nul(input);
+                command.args(self.args.iter().map(|arg| replace(arg, ARGUMENT_PLACEHOLDER, input)));
+            }
+            InputDelivery::Env => {
+                command.args(&self.args);
+                if let Some(name) = &self.input_env {
+                    command.env(name, OsStr::from_bytes(until_nul(input)));
+                }
+            }
+        }
+        Ok(command)
+    }
+
+    /// The default options of a sanitizer, followed by the ones the target
+    /// is given or would inherit.
+    fn sanitizer_options(&self, name: &str, defaults: &str) -> OsString {
+        let given = self.env.iter().rev().find(|(key, _)| key == name);
+        let options = match given {
+            Some((_, value)) => Some(value.clone()),
+            None if !self.clear_env => env::var_os(name),
+            None => None,
+        };
+        let mut merged = OsString::from(defaults);
+        if let Some(options) = options.filter(|options| !options.is_empty()) {
+            merged.push(":");
+            merged.push(options);
+        }
+        merged
+    }
+}
+
+pub(crate) fn limit_memory(bytes: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+    for resource in [libc::RLIMIT_AS, libc::RLIMIT_DATA] {
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// The part of the input a C string holds, as that is all the target could
+/// see of it anyway.
+fn until_nul(input: &[u8]) -> &[u8] {
+    input.split(|&byte| byte == 0).next().unwrap_or_default()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Replace every occurrence of the placeholder in the argument.
+fn replace(arg: &OsStr, placeholder: &str, with: &[u8]) -> OsString {
+    let mut rest = arg.as_bytes();
+    let mut replaced = Vec::new();
+    while let Some(i) = find(rest, placeholder.as_bytes()) {
+        replaced.extend_from_slice(&rest[..i]);
+        replaced.extend_from_slice(with);
+        rest = &rest[i + placeholder.len()..];
+    }
+    replaced.extend_from_slice(rest);
+    OsString::from_vec(replaced)
+}
+
+/// A file holding an input, like AFL's `.cur_input`. It lives in a private
+/// directory of its own, so other users cannot plant a symlink in its place,
+/// and is created once and rewritten through the same open file for every
+/// input. Both are removed on drop.
+#[derive(Debug)]
+pub(crate) struct InputFile {
+    dir: PathBuf,
+    path: PathBuf,
+    file: File,
+}
+
+impl InputFile {
+    pub(crate) fn create() -> io::Result<Self> {
+        let dir = private_temp_dir()?;
+        let path = dir.join("input");
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match file {
+            Ok(file) => Ok(Self { dir, path, file }),
+            Err(e) => {
+                let _ = fs::remove_dir(&dir);
+                Err(e)
+            }
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the input in the file.
+    pub(crate) fn write(&self, input: &[u8]) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.write_all_at(input, 0)
+    }
+
+    /// Another handle to the open file. It stays open after the file is
+    /// removed.
+    pub(crate) fn open(&self) -> io::Result<File> {
+        self.file.try_clone()
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+/// A new directory in the temporary directory that only we can access.
+fn private_temp_dir() -> io::Result<PathBuf> {
+    let template = env::temp_dir().join("fuzzer-XXXXXX");
+    let template = CString::new(template.into_os_string().into_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut template = template.into_bytes_with_nul();
+    if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn delivers_input_by_placeholder() {
+        let target = Target::new("target").with_args(["--parse", "-"]);
+        assert_eq!(target.input_delivery(), InputDelivery::Stdin);
+        let mut input_file = None;
+        let command = target.command(b"input", &mut input_file).unwrap();
+        assert_eq!(args(&command), ["--parse", "-"]);
+        assert!(input_file.is_none());
+
+        let target = Target::new("target").with_args(["--url={input}", "{input}"]);
+        assert_eq!(target.input_delivery(), InputDelivery::Argument);
+        let command = target.command(b"a b\0ignored", &mut None).unwrap();
+        assert_eq!(args(&command), ["--url=a b", "a b"]);
+
+        let target = Target::new("target").with_args(["--file=@@", "{input}"]);
+        assert_eq!(target.input_delivery(), InputDelivery::File);
+        let command = target.command(b"in a file", &mut input_file).unwrap();
+        let path = input_file.as_ref().unwrap().path.clone();
+        assert_eq!(fs::read(&path).unwrap(), b"in a file");
+        let expected = format!("--file={}", path.display());
+        assert_eq!(args(&command), [OsStr::new(&expected), OsStr::new("{input}")]);
+        drop(input_file);
+        assert!(!path.exists());
+
+        let target = Target::new("target").with_args(["@@"]).with_input_env("HTTP_PROXY");
+        assert_eq!(target.input_delivery(), InputDelivery::Env);
+        let mut input_file = None;
+        let command = target.command(b"proxy\0ignored", &mut input_file).unwrap();
+        assert_eq!(args(&command), ["@@"]);
+        let proxy = command.get_envs().find(|(key, _)| *key == "HTTP_PROXY");
+        assert_eq!(proxy, Some((OsStr::new("HTTP_PROXY"), Some(OsStr::new("proxy")))));
+        assert!(input_file.is_none());
+    }
+
+    #[test]
+    fn input_file_is_private_and_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let target = Target::new("target").with_args(["@@"]);
+        let mut input_file = None;
+        target.command(b"a longer first input", &mut input_file).unwrap();
+        let path = input_file.as_ref().unwrap().path().to_path_buf();
+        let dir = path.parent().unwrap().to_path_buf();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        let command = target.command(b"second", &mut input_file).unwrap();
+        assert_eq!(args(&command), [path.as_os_str()]);
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        drop(input_file);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn sanitizer_options_come_after_the_defaults() {
+        let mut target = Target::new("target").with_clear_env();
+        target.set_env("ASAN_OPTIONS", "detect_leaks=1");
+        let command = target.command(b"", &mut None).unwrap();
+        let env: Vec<_> = command.get_envs().collect();
+        let asan = env.iter().find(|(key, _)| *key == "ASAN_OPTIONS").unwrap();
+        let expected = "abort_on_error=1:symbolize=1:detect_leaks=0:detect_leaks=1";
+        assert_eq!(asan.1, Some(OsStr::new(expected)));
+        let ubsan = env.iter().find(|(key, _)| *key == "UBSAN_OPTIONS").unwrap();
+        assert_eq!(ubsan.1.unwrap(), SANITIZER_DEFAULTS[1].1);
+    }
+
+    #[test]
+    fn reports_what_is_delivered() {
+        let input_env = Target::new("target").with_input_env("LANG");
+        let argument = Target::new("target").with_args(["--lang={input}"]);
+        for target in [&input_env, &argument] {
+            assert_eq!(target.delivered(b"en_US"), Delivered::Whole);
+            assert_eq!(target.delivered(b"en\0US"), Delivered::TruncatedAtNul);
+            let too_large = vec![b'a'; MAX_STRING_LEN];
+            assert_eq!(target.delivered(&too_large), Delivered::TooLarge);
+            assert!(target.command(&too_large, &mut None).is_err());
+            // Only the part before the NUL byte has to fit.
+            let mut fits = too_large.clone();
+            fits[10] = 0;
+            assert_eq!(target.delivered(&fits), Delivered::TruncatedAtNul);
+        }
+
+        let stdin = Target::new("target");
+        assert_eq!(stdin.delivered(&[0; MAX_STRING_LEN]), Delivered::Whole);
+    }
+}
@@ -0,0 +1,97 @@
+//! A running target together with everything it started.
+//!
+//! Targets are started in a process group of their own, so that when we kill
+//! one, the subprocesses it started die with it instead of piling up as
+//! orphans that keep its pipes open.
+
+use shared_child::SharedChild;
+use std::{
+    io, mem,
+    process::{ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus},
+    sync::Mutex,
+};
+
+/// An execution of the target that can be killed from one thread while
+/// another one waits for it.
+pub(crate) trait Running: Send + Sync {
+    fn kill(&self);
+    fn wait(&self) -> io::Result<ExitStatus>;
+}
+
+pub(crate) struct TargetProcess {
+    child: SharedChild,
+    /// Whether the target was reaped. Until then its pid is taken, so it
+    /// cannot be reused as another process group id that we would kill by
+    /// mistake.
+    reaped: Mutex<bool>,
+}
+
+impl TargetProcess {
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        Ok(Self {
+            child: SharedChild::spawn(command)?,
+            reaped: Mutex::new(false),
+        })
+    }
+
+    pub fn take_stdin(&self) -> Option<ChildStdin> {
+        self.child.take_stdin()
+    }
+
+    pub fn take_stdout(&self) -> Option<ChildStdout> {
+        self.child.take_stdout()
+    }
+
+    pub fn take_stderr(&self) -> Option<ChildStderr> {
+        self.child.take_stderr()
+    }
+
+    /// Block until the target exits, without reaping it.
+    fn wait_for_exit(&self) -> io::Result<()> {
+        loop {
+            let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+            let result = unsafe {
+                libc::waitid(
+                    libc::P_PID,
+                    self.child.id() as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOWAIT,
+                )
+            };
+            if result == 0 {
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+
+    fn kill_group(&self) {
+        // The group may be empty already, which is fine.
+        unsafe { libc::killpg(self.child.id() as libc::pid_t, libc::SIGKILL) };
+    }
+}
+
+impl Running for TargetProcess {
+    /// Kill the target and every process in its group. Does nothing once the
+    /// target was reaped.
+    fn kill(&self) {
+        let reaped = self.reaped.lock().unwrap();
+        if !*reaped {
+            self.kill_group();
+        }
+    }
+
+    /// Wait for the target to exit, then kill whatever it left running in its
+    /// group before reaping it.
+    fn wait(&self) -> io::Result<ExitStatus> {
+        self.wait_for_exit()?;
+        let mut reaped = self.reaped.lock().unwrap();
+        self.kill_group();
+        let exit_status = self.child.wait()?;
+        *reaped = true;
+        Ok(exit_status)
+    }
+}
@@ -0,0 +1,135 @@
+//! Helpers shared by the integration tests.
+
+// Every test file uses only some of them.
+#![allow(dead_code)]
+
+use fuzzer::{Fuzzer, Observation};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// Cycles through a fixed list of inputs.
+#[derive(Default)]
+pub struct ListFuzzer {
+    inputs: Vec<Vec<u8>>,
+    next: usize,
+    new_coverage: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl ListFuzzer {
+    pub fn new<I: Into<Vec<u8>>>(inputs: impl IntoIterator<Item = I>) -> Self {
+        Self {
+            inputs: inputs.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Also record the inputs the fuzzer is told found new coverage.
+    pub fn recording_new_coverage(mut self, inputs: Arc<Mutex<Vec<String>>>) -> Self {
+        self.new_coverage = Some(inputs);
+        self
+    }
+}
+
+impl Fuzzer for ListFuzzer {
+    fn generate_input(&mut self) -> Vec<u8> {
+        let input = self.inputs[self.next % self.inputs.len()].clone();
+        self.next += 1;
+        input
+    }
+
+    fn observe(&mut self, observation: &Observation) {
+        if let Some(inputs) = &self.new_coverage {
+            if observation.new_coverage == Some(true) {
+                let input = String::from_utf8_lossy(observation.input).into_owned();
+                inputs.lock().unwrap().push(input);
+            }
+        }
+    }
+}
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// A C program of the tests, in tests/targets.
+fn test_target(source: &str) -> OsString {
+    manifest_dir().join("tests/targets").join(source).into()
+}
+
+/// Say that a test is skipped where it is seen: straight to stderr, which
+/// the test harness does not capture, unlike `eprintln!`.
+fn skip(reason: &str) {
+    let _ = writeln!(std::io::stderr(), "Skipping: {}", reason);
+}
+
+/// Compile with gcc into the tests' temporary directory. Returns `None`, and
+/// says the test is skipped, if gcc cannot build it (like when there is no
+/// gcc, or no libasan).
+fn gcc(output: &str, args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(output);
+    let args: Vec<_> = args.into_iter().collect();
+    let compiled = Command::new("gcc")
+        .args(&args)
+        .arg("-o")
+        .arg(&path)
+        .status();
+    if !compiled.is_ok_and(|status| status.success()) {
+        skip(&format!("could not compile {} with gcc {:?}", output, args));
+        return None;
+    }
+    Some(path)
+}
+
+/// Compile a test target with coverage instrumentation and our runtime.
+pub fn build_instrumented(source: &str) -> Option<PathBuf> {
+    let runtime = manifest_dir().join("resources/coverage/sancov_runtime.c");
+    let runtime = gcc("sancov_runtime.o", ["-c".into(), runtime.into()])?;
+    let executable = gcc(
+        source.trim_end_matches(".c"),
+        [
+            "-fsanitize-coverage=trace-pc".into(),
+            test_target(source),
+            runtime.into(),
+        ],
+    );
+    // gcc works, so the target must compile.
+    assert!(executable.is_some(), "could not compile {}", source);
+    executable
+}
+
+/// Compile a benchmark target with AddressSanitizer.
+pub fn build_with_asan(source: &str) -> Option<PathBuf> {
+    let source_path = manifest_dir().join("resources/benchmark").join(source);
+    let executable = format!("asan-{}", source.trim_end_matches(".c"));
+    gcc(
+        &executable,
+        ["-fsanitize=address".into(), "-g".into(), source_path.into()],
+    )
+}
+
+/// Compile a test target linked with the fork server runtime.
+pub fn build_with_fork_server(source: &str) -> Option<PathBuf> {
+    let fork_server = manifest_dir().join("resources/forkserver");
+    let executable = format!("forkserver-{}", source.trim_end_matches(".c"));
+    gcc(
+        &executable,
+        [
+            "-I".into(),
+            fork_server.clone().into(),
+            test_target(source),
+            fork_server.join("forkserver_runtime.c").into(),
+        ],
+    )
+}
+
+/// Compile a test harness as a shared library.
+pub fn build_harness(source: &str) -> Option<PathBuf> {
+    let library = format!("lib{}.so", source.trim_end_matches(".c"));
+    gcc(
+        &library,
+        ["-shared".into(), "-fPIC".into(), test_target(source)],
+    )
+}
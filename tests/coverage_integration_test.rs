@@ -0,0 +1,32 @@
+mod common;
+
+use common::{build_instrumented, ListFuzzer};
+use fuzzer::{Budget, ExitKind, MainRunner};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_coverage_reports_inputs_reaching_new_edges() {
+    let Some(executable) = build_instrumented("maze.c") else {
+        return;
+    };
+    let interesting = Arc::new(Mutex::new(Vec::new()));
+    let fuzzer = ListFuzzer::new(["x", "F", "FU", "x", "FUZZ"])
+        .recording_new_coverage(interesting.clone());
+    let mut runner = MainRunner::new(executable, fuzzer);
+    runner.enable_coverage().expect("could not set up coverage");
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(40),
+    });
+
+    let coverage = report.coverage.expect("coverage was enabled");
+    assert!(coverage.covered_edges > 0);
+    // "x", "F", "FU" and "FUZZ" each take a new path through the maze, and
+    // nothing after the first round does.
+    assert_eq!(*interesting.lock().unwrap(), ["x", "F", "FU", "FUZZ"]);
+    assert_eq!(coverage.interesting_execs, 4);
+    assert_eq!(report.crashes.len(), 1);
+    // SIGSEGV
+    assert_eq!(report.crashes[0].exit, ExitKind::Signal(11));
+}
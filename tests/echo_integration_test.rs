@@ -1,5 +1,12 @@
-use fuzzer::{DefaultRunner, Fuzzer, Runner};
+mod common;
+
+use common::ListFuzzer;
+use fuzzer::{
+    Budget, CrashPolicy, DefaultRunner, Event, ExitKind, Failure, FindingKind, Fuzzer,
+    FuzzingMode, MainFuzzer, MainRunner, ParallelRunner, Phase, Runner, Status, StopHandle, Target,
+};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 // Mock implementation of Fuzzer for the test
@@ -11,14 +18,14 @@ impl Fuzzer for MockFuzzer {
     }
 }
 
-#[test]
-fn test_runner_with_echo() {
+fn test_executable(name: &str) -> PathBuf {
     // Get the manifest directory
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
 
-    // Construct the full path to the echo.py script
+    // Construct the full path to the script
     let mut executable_path = PathBuf::from(manifest_dir);
-    executable_path.push("resources/test/echo.py");
+    executable_path.push("resources/test");
+    executable_path.push(name);
 
     // Verify the file exists
     assert!(
@@ -26,8 +33,12 @@ fn test_runner_with_echo() {
         "Executable file does not exist: {:?}",
         executable_path
     );
+    executable_path
+}
 
-    let executable = executable_path;
+#[test]
+fn test_runner_with_echo() {
+    let executable = test_executable("echo.py");
     let fuzzer = MockFuzzer;
     let mut runner = DefaultRunner::new(executable, Duration::from_secs(5), fuzzer);
 
@@ -38,8 +49,449 @@ fn test_runner_with_echo() {
         .expect("Failed to run the program");
 
     // Validate results
-    println!("{}", result.exit_code);
-    assert_eq!(result.exit_code, 0, "Program did not exit with code 0");
+    println!("{:?}", result.exit);
+    assert_eq!(result.exit, ExitKind::Code(0), "Program did not exit with code 0");
     assert_eq!(std::str::from_utf8(&result.stdout).unwrap().trim(), input.trim(), "Unexpected output");
     assert!(result.stderr.is_empty(), "Program wrote to stderr");
 }
+
+#[test]
+fn test_main_runner_reports_crash() {
+    let executable = test_executable("crash.py");
+    let mut runner = MainRunner::new(executable, MainFuzzer::new(FuzzingMode::Strings));
+
+    let result = runner
+        .run_with_input(b"anything\n")
+        .expect("Failed to run the program");
+
+    assert_eq!(result.exit, ExitKind::Code(1));
+}
+
+#[test]
+fn test_runner_reports_timeout() {
+    let executable = test_executable("timeout-test.py");
+    let mut runner = DefaultRunner::new(executable, Duration::from_millis(500), MockFuzzer);
+
+    let result = runner
+        .run_with_input(b"no letter A here\n")
+        .expect("Failed to run the program");
+
+    assert_eq!(result.exit, ExitKind::Timeout);
+}
+
+#[test]
+fn test_targets_that_do_not_read_a_large_input_time_out() {
+    let target = Target::new("sleep").with_args(["10"]);
+    let mut runner = MainRunner::with_target(target, MockFuzzer);
+    runner.set_timeout(Duration::from_millis(200));
+
+    let start = std::time::Instant::now();
+    let result = runner
+        .run_with_input(&[b'a'; 1 << 20])
+        .expect("Failed to run the program");
+
+    assert_eq!(result.exit, ExitKind::Timeout);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_continuous_run_groups_crashes() {
+    let executable = test_executable("crash-if-input-length-one.py");
+    let fuzzer = ListFuzzer::new(["ab\n", "a\n", "bb\n", "c\n"]);
+    let mut runner = MainRunner::new(executable, fuzzer);
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(4),
+    });
+
+    // Both crashes exit the same way with the same (empty) stderr, so they
+    // are the same bug.
+    assert_eq!(report.execs, 4);
+    assert_eq!(report.total_crashes, 2);
+    assert_eq!(report.crashes.len(), 1);
+    assert_eq!(report.crashes[0].input, b"a\n");
+    assert_eq!(report.crashes[0].exit, ExitKind::Code(1));
+}
+
+#[test]
+fn test_continuous_run_stops_on_time_budget() {
+    let executable = test_executable("echo.py");
+    let mut runner = MainRunner::new(executable, MockFuzzer);
+
+    let start = std::time::Instant::now();
+    let report = runner.run_continuous(Budget {
+        max_time: Some(Duration::from_millis(300)),
+        max_execs: None,
+    });
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(report.crashes.is_empty());
+}
+
+#[test]
+fn test_input_delivered_in_file_or_argument() {
+    let target = Target::new("cat").with_args(["@@"]);
+    let mut runner = MainRunner::with_target(target, MockFuzzer);
+    let result = runner.run_with_input(b"from a file").expect("Failed to run cat");
+    assert_eq!(result.exit, ExitKind::Code(0));
+    assert_eq!(result.stdout, b"from a file");
+
+    let target = Target::new("echo").with_args(["-n", "--input={input}"]);
+    let mut runner = MainRunner::with_target(target, MockFuzzer);
+    let result = runner.run_with_input(b"as an argument").expect("Failed to run echo");
+    assert_eq!(result.exit, ExitKind::Code(0));
+    assert_eq!(result.stdout, b"--input=as an argument");
+}
+
+#[test]
+fn test_input_delivered_in_env() {
+    let too_large = "a".repeat(200_000);
+    let fuzzer = ListFuzzer::new(["ok".to_string(), "crash\0ignored".to_string(), too_large]);
+    let target = Target::new("sh")
+        .with_args(["-c", "test \"$FUZZ\" != crash"])
+        .with_input_env("FUZZ");
+    let mut runner = MainRunner::with_target(target, fuzzer);
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(5),
+    });
+
+    // The skipped input spends the budget like an execution.
+    assert_eq!(report.execs, 4);
+    assert_eq!(report.truncated_inputs, 2);
+    assert_eq!(report.skipped_inputs, 1);
+    assert_eq!(report.total_crashes, 2);
+    assert_eq!(report.crashes[0].input, b"crash\0ignored");
+}
+
+#[test]
+fn test_only_too_large_inputs_can_still_be_stopped() {
+    let too_large = "a".repeat(200_000);
+    let target = Target::new("true").with_input_env("FUZZ");
+    let mut runner = MainRunner::with_target(target, ListFuzzer::new([too_large]));
+    let stop_handle = StopHandle::default();
+    runner.set_stop_handle(stop_handle.clone());
+    let stopper = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        stop_handle.stop();
+    });
+
+    let report = runner.run_continuous(Budget::default());
+
+    stopper.join().unwrap();
+    assert!(report.interrupted);
+    assert_eq!(report.execs, 0);
+    assert!(report.skipped_inputs > 0);
+}
+
+#[test]
+fn test_only_too_large_inputs_spend_the_execution_budget() {
+    let too_large = "a".repeat(200_000);
+    let target = Target::new("true").with_input_env("FUZZ");
+    let mut runner = MainRunner::with_target(target, ListFuzzer::new([too_large]));
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(10),
+    });
+
+    assert_eq!(report.execs, 0);
+    assert_eq!(report.skipped_inputs, 10);
+}
+
+#[test]
+fn test_targets_that_cannot_start_fail_the_run() {
+    let target = Target::new("/nonexistent/target");
+    let mut runner = MainRunner::with_target(target, ListFuzzer::new(["a"]));
+
+    let report = runner.run_continuous(Budget::default());
+
+    assert_eq!(report.execs, 0);
+    let error = report.error.expect("the run did not fail");
+    assert!(error.contains("Could not run the target"), "{}", error);
+}
+
+#[test]
+fn test_stopping_kills_a_single_input() {
+    let target = Target::new("sh").with_args(["-c", "sleep 10"]);
+    let mut runner = MainRunner::with_target(target, MockFuzzer);
+    runner.set_timeout(Duration::from_secs(30));
+    let stop_handle = StopHandle::default();
+    runner.set_stop_handle(stop_handle.clone());
+    let stopper = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        stop_handle.stop();
+    });
+
+    let start = std::time::Instant::now();
+    let result = runner.run_with_input(b"");
+
+    stopper.join().unwrap();
+    assert!(result.is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    // Later inputs are not run at all.
+    assert!(runner.run_with_input(b"").is_err());
+}
+
+#[test]
+fn test_parallel_workers_share_budget_and_crashes() {
+    let executable = test_executable("crash-if-input-length-one.py");
+    let workers = (0..3)
+        .map(|_| MainRunner::new(executable.clone(), ListFuzzer::new(["ab\n", "a\n"])))
+        .collect();
+    let mut runner = ParallelRunner::new(workers);
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(10),
+    });
+
+    assert_eq!(report.execs, 10);
+    assert!(report.total_crashes >= 3);
+    assert_eq!(report.crashes.len(), 1);
+    assert_eq!(report.crashes[0].input, b"a\n");
+}
+
+#[test]
+fn test_parallel_workers_stop_on_time_budget() {
+    let workers = (0..4)
+        .map(|_| MainRunner::with_target(Target::new("cat"), MockFuzzer))
+        .collect();
+    let mut runner = ParallelRunner::new(workers);
+
+    let start = std::time::Instant::now();
+    let report = runner.run_continuous(Budget {
+        max_time: Some(Duration::from_millis(300)),
+        max_execs: None,
+    });
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(report.execs > 0);
+}
+
+#[test]
+fn test_stop_handle_interrupts_run() {
+    let mut runner = MainRunner::with_target(Target::new("cat"), MockFuzzer);
+    let stop_handle = StopHandle::default();
+    runner.set_stop_handle(stop_handle.clone());
+
+    let stopper = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        stop_handle.stop();
+    });
+    let report = runner.run_continuous(Budget::default());
+    stopper.join().unwrap();
+
+    assert!(report.interrupted);
+    assert!(report.execs > 0);
+    // Once stopped, later runs stop right away.
+    let report = runner.run_until_crash(Budget::default());
+    assert!(report.interrupted);
+    assert_eq!(report.execs, 0);
+}
+
+#[test]
+fn test_run_until_crash_stops_on_budget() {
+    let mut runner = MainRunner::with_target(Target::new("cat"), MockFuzzer);
+
+    let report = runner.run_until_crash(Budget {
+        max_time: None,
+        max_execs: Some(3),
+    });
+
+    assert_eq!(report.execs, 3);
+    assert!(!report.interrupted);
+    assert!(report.crashes.is_empty());
+}
+
+#[test]
+fn test_hangs_are_confirmed_and_reported() {
+    let script = "read input; case $input in slow) exec sleep 0.4;; hang) exec sleep 10;; esac";
+    let target = Target::new("sh").with_args(["-c", script]);
+    let fuzzer = ListFuzzer::new(["slow\n", "hang\n", "fast\n"]);
+    let mut runner = MainRunner::with_target(target, fuzzer);
+    runner.set_timeout(Duration::from_millis(200));
+    runner.report_hangs();
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(3),
+    });
+
+    // The slow input finishes with a longer timeout, so it is not a hang.
+    assert_eq!(report.timeouts, 2);
+    assert_eq!(report.total_hangs, 1);
+    assert_eq!(report.hangs[0].input, b"hang\n");
+    assert_eq!(report.hangs[0].exit, ExitKind::Timeout);
+    assert!(report.crashes.is_empty());
+}
+
+#[test]
+fn test_out_of_memory_is_its_own_finding() {
+    let script = "import sys; bytearray(int(sys.stdin.read()))";
+    let target = Target::new("python3")
+        .with_args(["-c", script])
+        .with_mem_limit(256 << 20);
+    let fuzzer = ListFuzzer::new(["1000", "4000000000", "x"]);
+    let mut runner = MainRunner::with_target(target, fuzzer);
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(3),
+    });
+
+    assert_eq!(report.total_out_of_memory, 1);
+    assert_eq!(report.out_of_memory[0].input, b"4000000000");
+    assert_eq!(report.out_of_memory[0].kind, FindingKind::OutOfMemory);
+    // The ValueError is an ordinary crash.
+    assert_eq!(report.total_crashes, 1);
+    assert_eq!(report.crashes[0].input, b"x");
+}
+
+/// Whether the process exists and was not killed yet. Killed orphans may stay
+/// zombies until init gets to them.
+fn is_running(pid: &str) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat.rsplit(") ").next().is_some_and(|rest| !rest.starts_with('Z')),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn test_subprocesses_are_killed_with_the_target() {
+    // The target prints the pid of a subprocess that outlives it, which also
+    // keeps its stdout open.
+    let timing_out = "sleep 30 & echo $!; wait";
+    let exiting = "sleep 30 & echo $!";
+    for (script, exit) in [(timing_out, ExitKind::Timeout), (exiting, ExitKind::Code(0))] {
+        let target = Target::new("sh").with_args(["-c", script]);
+        let mut runner = MainRunner::with_target(target, MockFuzzer);
+        runner.set_timeout(Duration::from_millis(200));
+
+        let start = std::time::Instant::now();
+        let result = runner.run_with_input(b"").expect("Failed to run the program");
+
+        assert_eq!(result.exit, exit);
+        assert!(start.elapsed() < Duration::from_secs(10), "waited for the subprocess");
+        let pid = String::from_utf8(result.stdout).unwrap();
+        let pid = pid.trim();
+        // It was sent SIGKILL, but dying takes a moment.
+        let killed = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            !is_running(pid)
+        });
+        assert!(killed, "subprocess {} is still running", pid);
+    }
+}
+
+#[test]
+fn test_crash_policy_ignores_error_exits() {
+    let script = "read input; case $input in bad) exit 1;; segv) kill -SEGV $$;; esac";
+    let target = Target::new("sh").with_args(["-c", script]);
+    let fuzzer = ListFuzzer::new(["bad\n", "segv\n", "fine\n"]);
+    let mut runner = MainRunner::with_target(target, fuzzer);
+    runner.set_crash_policy(CrashPolicy {
+        crash_on: vec![Failure::Signal, Failure::Sanitizer],
+        ignored_exit_codes: Vec::new(),
+    });
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(3),
+    });
+
+    assert_eq!(report.total_crashes, 1);
+    assert_eq!(report.crashes[0].input, b"segv\n");
+    assert_eq!(report.crashes[0].exit, ExitKind::Signal(11));
+    assert_eq!(report.ignored_failures, 1);
+}
+
+#[test]
+fn test_target_environment_is_cleared_and_set() {
+    let mut target = Target::new("sh").with_args(["-c", "env"]).with_clear_env();
+    target.set_env("FUZZ_TEST_VAR", "value");
+    target.set_env("UBSAN_OPTIONS", "print_stacktrace=0");
+    let mut runner = MainRunner::with_target(target, MockFuzzer);
+
+    let result = runner.run_with_input(b"").expect("Failed to run the program");
+
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    let env: Vec<&str> = stdout.lines().collect();
+    assert!(env.contains(&"FUZZ_TEST_VAR=value"));
+    assert!(env.contains(&"ASAN_OPTIONS=abort_on_error=1:symbolize=1:detect_leaks=0"));
+    assert!(env.contains(
+        &"UBSAN_OPTIONS=halt_on_error=1:abort_on_error=1:print_stacktrace=1:symbolize=1:\
+          print_stacktrace=0"
+    ));
+    assert!(!env.iter().any(|line| line.starts_with("HOME=")));
+}
+
+#[test]
+fn test_status_listeners_follow_the_run() {
+    let executable = test_executable("crash-if-input-length-one.py");
+    let mut runner = MainRunner::new(executable, MainFuzzer::with_seed(FuzzingMode::Strings, 1));
+    let statuses: Arc<Mutex<Vec<Status>>> = Arc::default();
+    let seen = statuses.clone();
+    runner.add_status_listener(Duration::from_millis(50), move |status| {
+        seen.lock().unwrap().push(status.clone());
+    });
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(20),
+    });
+
+    let statuses = statuses.lock().unwrap();
+    assert!(statuses.len() >= 2, "{:?}", statuses);
+    assert!(statuses[..statuses.len() - 1].iter().all(|status| !status.done));
+    let last = statuses.last().unwrap();
+    assert!(last.done);
+    assert_eq!(last.execs, report.execs);
+    assert_eq!(last.total_crashes, report.total_crashes);
+    assert_eq!(last.unique_crashes, report.crashes.len());
+    assert!(matches!(last.phase, Some(Phase::Predefined { index: 20, .. })));
+    // The empty input and several one byte inputs come first, and crash.
+    assert_eq!(last.since_last_finding.is_some(), report.total_crashes > 0);
+}
+
+#[test]
+fn test_event_listeners_see_the_run_and_its_findings() {
+    let executable = test_executable("crash-if-input-length-one.py");
+    let mut runner = ParallelRunner::new(
+        (0..2)
+            .map(|i| {
+                let fuzzer = MainFuzzer::with_seed(FuzzingMode::Strings, i);
+                MainRunner::new(executable.clone(), fuzzer)
+            })
+            .collect(),
+    );
+    let events: Arc<Mutex<Vec<String>>> = Arc::default();
+    let seen = events.clone();
+    runner.add_event_listener(move |event| {
+        let event = match event {
+            Event::Started { workers } => format!("started {}", workers),
+            Event::Found { finding, saved_to } => {
+                assert!(saved_to.is_none());
+                format!("found {} {}", finding.kind.name(), finding.input.len())
+            }
+            Event::NewCoverage { .. } => "new coverage".to_string(),
+            Event::Stopped { report } => format!("stopped {}", report.crashes.len()),
+        };
+        seen.lock().unwrap().push(event);
+    });
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(20),
+    });
+
+    let events = events.lock().unwrap();
+    assert!(!report.crashes.is_empty());
+    let mut expected = vec!["started 2".to_string()];
+    // New crashes are told about as they are found, in the report's order.
+    expected.extend(report.crashes.iter().map(|crash| format!("found crash {}", crash.input.len())));
+    expected.push(format!("stopped {}", report.crashes.len()));
+    assert_eq!(*events, expected);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::{build_with_fork_server, ListFuzzer};
+use fuzzer::{Budget, ExitKind, MainRunner, Target};
+use std::time::Duration;
+
+fn run(target: Target) -> fuzzer::Report {
+    run_inputs(target, vec!["fine\n", "crash\n", "hang\n", "fine\n"])
+}
+
+fn run_inputs(target: Target, inputs: Vec<&'static str>) -> fuzzer::Report {
+    let execs = inputs.len() as u64;
+    let fuzzer = ListFuzzer::new(inputs);
+    let mut runner = MainRunner::with_target(target, fuzzer);
+    runner.set_timeout(Duration::from_millis(200));
+    runner.enable_fork_server();
+    runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(execs),
+    })
+}
+
+#[test]
+fn test_fork_server_runs_every_input() {
+    let Some(executable) = build_with_fork_server("fork_me.c") else {
+        return;
+    };
+    for target in [Target::new(&executable), Target::new(&executable).with_args(["@@"])] {
+        let report = run(target);
+
+        assert_eq!(report.execs, 4);
+        assert_eq!(report.timeouts, 1);
+        assert_eq!(report.total_crashes, 1);
+        let crash = &report.crashes[0];
+        assert_eq!(crash.input, b"crash\n");
+        assert_eq!(crash.exit, ExitKind::Signal(6));
+        // The crashing copy was forked by the fork server, not by us.
+        let parent: u32 = crash.stderr.trim().strip_prefix("parent ").unwrap().parse().unwrap();
+        assert_ne!(parent, std::process::id());
+    }
+}
+
+#[test]
+fn test_inputs_that_kill_the_fork_server_run_again_with_exec() {
+    let Some(executable) = build_with_fork_server("fork_me.c") else {
+        return;
+    };
+    let inputs = vec!["fine\n", "kill-server\n", "crash\n"];
+    let report = run_inputs(Target::new(&executable), inputs);
+
+    // The input is not lost: it runs again, without the fork server.
+    assert_eq!(report.execs, 3);
+    assert_eq!(report.total_crashes, 1);
+    let parent = format!("parent {}", std::process::id());
+    assert_eq!(report.crashes[0].stderr.trim(), parent);
+}
+
+#[test]
+fn test_subprocesses_are_killed_with_a_forked_copy() {
+    let Some(executable) = build_with_fork_server("fork_me.c") else {
+        return;
+    };
+    let pid_file = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("fork_me.pid");
+    let _ = std::fs::remove_file(&pid_file);
+    let mut target = Target::new(&executable);
+    target.set_env("FORK_ME_PID_FILE", &pid_file);
+    let report = run_inputs(target, vec!["spawn-hang\n"]);
+
+    assert_eq!(report.timeouts, 1);
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let stat = format!("/proc/{}/stat", pid.trim());
+    // It was sent SIGKILL, but dying takes a moment.
+    let killed = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        match std::fs::read_to_string(&stat) {
+            Ok(stat) => stat.rsplit(") ").next().is_some_and(|rest| rest.starts_with('Z')),
+            Err(_) => true,
+        }
+    });
+    assert!(killed, "subprocess {} is still running", pid.trim());
+}
+
+#[test]
+fn test_targets_without_fork_server_run_with_exec() {
+    let script = "read input; case $input in crash) echo \"parent $PPID\" >&2; kill -ABRT $$;; \
+                  hang) exec sleep 10;; esac";
+    let report = run(Target::new("sh").with_args(["-c", script]));
+
+    assert_eq!(report.execs, 4);
+    assert_eq!(report.timeouts, 1);
+    assert_eq!(report.total_crashes, 1);
+    assert_eq!(report.crashes[0].stderr.trim(), format!("parent {}", std::process::id()));
+}
+
+#[test]
+fn test_persistent_mode_runs_many_inputs_per_process() {
+    let Some(executable) = build_with_fork_server("persistent.c") else {
+        return;
+    };
+    let report = run_inputs(Target::new(&executable), vec!["fine", "fine", "fine", "crash"]);
+    assert_eq!(report.execs, 4);
+    assert_eq!(report.total_crashes, 1);
+    assert_eq!(report.crashes[0].input, b"crash");
+    assert_eq!(report.crashes[0].stderr.trim(), "input 4 of this process");
+
+    // Every input has its own timeout, and a process that timed out is
+    // replaced.
+    let report = run_inputs(Target::new(&executable), vec!["fine", "hang", "fine", "crash"]);
+    assert_eq!(report.timeouts, 1);
+    assert_eq!(report.crashes[0].stderr.trim(), "input 2 of this process");
+
+    // So is a process that ran its maximum number of inputs.
+    let target = Target::new(&executable).with_args(["2"]);
+    let report = run_inputs(target, vec!["fine", "fine", "fine", "crash"]);
+    assert_eq!(report.timeouts, 0);
+    assert_eq!(report.crashes[0].stderr.trim(), "input 2 of this process");
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{build_harness, ListFuzzer};
+use fuzzer::{Budget, ExitKind, MainRunner, Runner, Target};
+use std::time::Duration;
+
+#[test]
+fn test_harness_crashes_are_isolated() {
+    let Some(library) = build_harness("harness.c") else {
+        return;
+    };
+    let fuzzer = ListFuzzer::new(["fine", "crash", "hang", "fine", "count"]);
+    let mut runner = MainRunner::with_harness(Target::new(library), fuzzer).unwrap();
+    runner.set_timeout(Duration::from_millis(200));
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(5),
+    });
+
+    assert_eq!(report.execs, 5);
+    assert_eq!(report.timeouts, 1);
+    assert_eq!(report.total_crashes, 2);
+    let crash = report.crashes.iter().find(|crash| crash.input == b"crash").unwrap();
+    assert_eq!(crash.exit, ExitKind::Signal(11));
+    // The child killed on the hang was replaced, and the new one ran both
+    // inputs after it.
+    let count = report.crashes.iter().find(|crash| crash.input == b"count").unwrap();
+    assert_eq!(count.exit, ExitKind::Signal(6));
+    assert!(count.stderr.contains("calls 2, initialized 1"), "{}", count.stderr);
+}
+
+#[test]
+fn test_harness_runs_single_inputs() {
+    let Some(library) = build_harness("harness.c") else {
+        return;
+    };
+    let mut runner = MainRunner::with_harness(Target::new(library), ListFuzzer::default()).unwrap();
+
+    let fine = runner.run_with_input(b"fine").unwrap();
+    assert_eq!(fine.exit, ExitKind::Code(0));
+    let crash = runner.run_with_input(b"crash").unwrap();
+    assert!(runner.is_crash(&crash));
+    let count = runner.run_with_input(b"count").unwrap();
+    assert!(count.stderr.contains("calls 1"), "{}", count.stderr);
+}
+
+#[test]
+fn test_libraries_without_a_harness_are_rejected() {
+    let Some(library) = build_harness("maze.c") else {
+        return;
+    };
+    let error = MainRunner::with_harness(Target::new(library), ListFuzzer::default()).err();
+    assert!(error.unwrap().to_string().contains("LLVMFuzzerTestOneInput"));
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::{build_with_asan, ListFuzzer};
+use fuzzer::{AccessKind, Budget, MainRunner, Sanitizer};
+
+#[test]
+fn test_asan_reports_are_parsed_and_bucketed() {
+    let Some(executable) = build_with_asan("buffer_overflow.c") else {
+        return;
+    };
+    // Both long inputs overflow the same buffer, by a different amount.
+    let inputs = vec!["short\n".to_string(), "A".repeat(40), "A".repeat(60)];
+    let mut runner = MainRunner::new(executable, ListFuzzer::new(inputs));
+
+    let report = runner.run_continuous(Budget {
+        max_time: None,
+        max_execs: Some(3),
+    });
+
+    assert_eq!(report.total_crashes, 2);
+    assert_eq!(report.crashes.len(), 1);
+    let sanitizer = report.crashes[0].sanitizer.as_ref().expect("no sanitizer report");
+    assert_eq!(sanitizer.sanitizer, Sanitizer::Address);
+    assert_eq!(sanitizer.bug_type, "stack-buffer-overflow");
+    assert_eq!(sanitizer.access.map(|access| access.kind), Some(AccessKind::Write));
+    assert_eq!(sanitizer.frames[..2], ["vulnerable_function", "main"]);
+    assert!(report.to_string().contains("AddressSanitizer: stack-buffer-overflow"));
+}
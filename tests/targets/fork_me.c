@@ -0,0 +1,46 @@
+/* Reads its input from the file given as its argument, or from stdin. Crashes
+ * on "crash", telling its parent's pid, and never finishes on "hang". On
+ * "kill-server", kills the fork server that forked it, if there is one. On
+ * "spawn-hang", starts a subprocess that never finishes either, writes its pid
+ * to the file in $FORK_ME_PID_FILE, and hangs. */
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+int main(int argc, char **argv) {
+    FILE *file = argc > 1 ? fopen(argv[1], "r") : stdin;
+    char input[16] = {0};
+    if (!file || !fgets(input, sizeof(input), file)) {
+        return 0;
+    }
+    if (strncmp(input, "crash", 5) == 0) {
+        fprintf(stderr, "parent %d\n", (int)getppid());
+        abort();
+    }
+    if (strncmp(input, "kill-server", 11) == 0 && getenv("FUZZER_INPUT_SHM_ID")) {
+        kill(getppid(), SIGKILL);
+        return 0;
+    }
+    if (strncmp(input, "spawn-hang", 10) == 0) {
+        pid_t child = fork();
+        if (child == 0) {
+            for (;;) {
+                pause();
+            }
+        }
+        FILE *pid_file = fopen(getenv("FORK_ME_PID_FILE"), "w");
+        fprintf(pid_file, "%d\n", (int)child);
+        fclose(pid_file);
+        for (;;) {
+            pause();
+        }
+    }
+    if (strncmp(input, "hang", 4) == 0) {
+        for (;;) {
+            pause();
+        }
+    }
+    return 0;
+}
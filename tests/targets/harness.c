@@ -0,0 +1,35 @@
+/* A libFuzzer harness, built as a shared library. Crashes on "crash", never
+ * returns on "hang", and on "count" aborts after telling how many inputs it
+ * was called with, and whether it was initialized. */
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+static int initialized = 0;
+static int calls = 0;
+
+int LLVMFuzzerInitialize(int *argc, char ***argv) {
+    (void)argc;
+    (void)argv;
+    initialized++;
+    return 0;
+}
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {
+    calls++;
+    if (size == 5 && memcmp(data, "crash", 5) == 0) {
+        *(volatile int *)0 = 0;
+    }
+    if (size == 4 && memcmp(data, "hang", 4) == 0) {
+        for (;;) {
+            pause();
+        }
+    }
+    if (size == 5 && memcmp(data, "count", 5) == 0) {
+        fprintf(stderr, "calls %d, initialized %d\n", calls, initialized);
+        abort();
+    }
+    return 0;
+}
@@ -0,0 +1,20 @@
+/* Crashes only on the input "FUZZ", one byte at a time, so it is hard to find
+ * without coverage feedback. */
+#include <stdio.h>
+
+int main(void) {
+    char input[16] = {0};
+    if (!fgets(input, sizeof(input), stdin)) {
+        return 0;
+    }
+    if (input[0] == 'F') {
+        if (input[1] == 'U') {
+            if (input[2] == 'Z') {
+                if (input[3] == 'Z') {
+                    *(volatile int *)0 = 0;
+                }
+            }
+        }
+    }
+    return 0;
+}
@@ -0,0 +1,29 @@
+/* Runs its inputs in persistent mode, at most as many per process as its
+ * argument says. Crashes on "crash", telling how many inputs this process
+ * ran, and never finishes on "hang". */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+#include "fuzzer_loop.h"
+
+int main(int argc, char **argv) {
+    unsigned int max_inputs = argc > 1 ? atoi(argv[1]) : 1000;
+    int inputs = 0;
+    while (fuzzer_loop(max_inputs)) {
+        size_t len;
+        const unsigned char *input = fuzzer_input(&len);
+        inputs++;
+        if (len >= 5 && memcmp(input, "crash", 5) == 0) {
+            fprintf(stderr, "input %d of this process\n", inputs);
+            abort();
+        }
+        if (len >= 4 && memcmp(input, "hang", 4) == 0) {
+            for (;;) {
+                pause();
+            }
+        }
+    }
+    return 0;
+}
@@ -3,6 +3,7 @@
 import os
 import subprocess
 import argparse
+import json
 import sys
 
 # ANSI escape codes for colors
@@ -75,6 +76,21 @@ def process_testcases(root_folder, custom_cmake_command):
 
     return all_targets_built
 
+# The version of the fuzzer's --stats-file format this script reads.
+STATS_VERSION = 1
+
+def print_stats(stats_path):
+    try:
+        with open(stats_path) as stats_file:
+            stats = json.load(stats_file)
+    except (OSError, ValueError):
+        return
+    if stats.get("version") != STATS_VERSION:
+        print(colorize(f"Unknown statistics version in {stats_path}", Colors.YELLOW))
+        return
+    print(colorize(f"{stats['execs']} executions ({stats['execs_per_sec']:.0f}/sec), "
+                   f"{stats['unique_crashes']} unique crashes", Colors.CYAN))
+
 def run_fuzzer_tests(root_folder, fuzzer_path):
     for subdir in immediate_subfolders(root_folder):
         main_executable = os.path.join(subdir, 'main')
@@ -82,8 +98,11 @@ def run_fuzzer_tests(root_folder, fuzzer_path):
 
         if os.path.isfile(main_executable): 
             print(colorize(f"Running fuzzer on {main_executable}", Colors.CYAN))
+            stats_path = os.path.join(subdir, 'fuzzer-stats.json')
+            if os.path.exists(stats_path):
+                os.remove(stats_path)
             try:
-                run_command([fuzzer_path, '--urls', main_executable], timeout=30)
+                run_command([fuzzer_path, '--urls', '--stats-file', stats_path, main_executable], timeout=30)
             except subprocess.CalledProcessError as e:
                 if e.stderr:
                     print(colorize(f"Error running fuzzer on {main_executable}: {e.stderr.strip()}", Colors.RED))
@@ -91,12 +110,13 @@ def run_fuzzer_tests(root_folder, fuzzer_path):
                     print(colorize(f"Error running fuzzer on {main_executable}: {e.stderr.strip()}", Colors.YELLOW))
             except subprocess.TimeoutExpired:
                 print(colorize(f"Timeout running fuzzer on {main_executable}", Colors.RED))
+            print_stats(stats_path)
 
 
 def clean_make_cmake(root_folder):
     for subdir in immediate_subfolders(root_folder):
         run_command("make clean", cwd=subdir, shell=True)
-    run_command('find . \( -name "*.so" -o -name "*.a" -o -name "main" -o -name "CMakeCache.txt"  -o -name "CMakeFiles" \) -exec rm -rf {} +', shell=True)
+    run_command('find . \( -name "*.so" -o -name "*.a" -o -name "main" -o -name "CMakeCache.txt" -o -name "fuzzer-stats.json" -o -name "CMakeFiles" \) -exec rm -rf {} +', shell=True)
     
 
 